@@ -0,0 +1,424 @@
+//! 无界面的命令行入口，与窗口版共用会话与主题库。
+//! 结果以 JSON 输出到 stdout（`asar cat` 除外），日志与进度输出到 stderr。
+
+use std::{
+    env, fs,
+    io::{self, Write},
+    path::{Component, Path, PathBuf},
+    process::ExitCode,
+};
+
+use serde_json::{json, Value};
+use tokio_util::sync::CancellationToken;
+use wal_assistant_lark_lib::{
+    error::{WalError, WalResult},
+    shared::{lark_variant_session, set_app_data_dir},
+    utils::{
+        doctor,
+        file::{backup::find_backups_recursively, journal},
+        lark::{
+            explore::open_installed_asar,
+            find::LarkFinder,
+            wrappers::session::{self, LarkSession, PatchPlan},
+        },
+        log::{file::RotatingFile, logger},
+        platform::process::is_process_running,
+        settings,
+        theme::{
+            merge::merge_themes,
+            parse_theme,
+            script::make_styles_script,
+            style::{make_html_injection, make_stylesheet},
+            ThemePatch, ThemePatchTarget, WalTheme,
+        },
+        theme_store,
+    },
+};
+
+/// 与 tauri.conf.json 中的 identifier 一致，决定应用数据目录
+const APP_IDENTIFIER: &str = "com.miku.wal";
+
+const USAGE: &str = "usage: wal-cli [--variant <id>] [--data-dir <dir>] <command>
+
+commands:
+  status                       install location, process and backup state
+  apply [--dry-run] [<theme>...]
+                               restore, then apply themes (ids in the library or YAML paths);
+                               without arguments applies the themes enabled in the library;
+                               --dry-run only prints the expanded patch targets
+  restore                      restore all backups
+  list-backups                 list backup files
+  doctor                       run all diagnostic checks
+  asar ls <asar>               list files in an asar
+  asar cat <asar> <path>       write a file from an asar to stdout
+  asar extract <asar> <dir>    extract an asar into a directory";
+
+/// 失败时输出的错误；context 中的字段并入错误 JSON
+struct CliError {
+    error: WalError,
+    context: Value,
+}
+
+impl From<WalError> for CliError {
+    fn from(error: WalError) -> Self {
+        Self {
+            error,
+            context: Value::Null,
+        }
+    }
+}
+
+struct Options {
+    variant: Option<String>,
+    data_dir: PathBuf,
+    command: Vec<String>,
+}
+
+fn main() -> ExitCode {
+    let options = match parse_args(env::args().skip(1).collect()) {
+        Some(options) => options,
+        None => {
+            eprintln!("{}", USAGE);
+            return ExitCode::from(2);
+        }
+    };
+    set_app_data_dir(options.data_dir.clone());
+    if let Ok(file) = RotatingFile::open(&options.data_dir) {
+        let _ = logger::set_log_file(file);
+    }
+    if let Ok(settings) = settings::read_settings(&options.data_dir) {
+        settings::apply_settings(settings);
+    }
+
+    match run(&options) {
+        Ok(Some(data)) => {
+            println!("{}", json!({ "ok": true, "data": data }));
+            ExitCode::SUCCESS
+        }
+        Ok(None) => ExitCode::SUCCESS,
+        Err(e) => {
+            let mut output = json!({ "ok": false, "error": format!("{:?}", e.error) });
+            if let (Some(output), Value::Object(context)) = (output.as_object_mut(), e.context) {
+                output.extend(context);
+            }
+            println!("{}", output);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn parse_args(args: Vec<String>) -> Option<Options> {
+    let mut variant = None;
+    let mut data_dir = None;
+    let mut command = Vec::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--variant" => variant = Some(args.next()?),
+            "--data-dir" => data_dir = Some(PathBuf::from(args.next()?)),
+            "-h" | "--help" => return None,
+            _ => command.push(arg),
+        }
+    }
+    if command.is_empty() {
+        return None;
+    }
+    let data_dir = data_dir
+        .or_else(|| env::var_os("APPDATA").map(|dir| PathBuf::from(dir).join(APP_IDENTIFIER)))?;
+    Some(Options {
+        variant,
+        data_dir,
+        command,
+    })
+}
+
+/// 返回 None 表示结果已直接写到 stdout
+fn run(options: &Options) -> Result<Option<Value>, CliError> {
+    let variant = options.variant.as_deref();
+    let args = options
+        .command
+        .iter()
+        .map(|arg| arg.as_str())
+        .collect::<Vec<_>>();
+    match args.as_slice() {
+        ["status"] => Ok(Some(status(variant)?)),
+        ["apply", "--dry-run", themes @ ..] => {
+            apply(&options.data_dir, variant, themes, true).map(Some)
+        }
+        ["apply", themes @ ..] => apply(&options.data_dir, variant, themes, false).map(Some),
+        ["restore"] => {
+            journal::recover_interrupted_applies(&options.data_dir);
+            with_session(variant, |session| session.restore_all_backups())?;
+            Ok(Some(Value::Null))
+        }
+        ["list-backups"] => Ok(to_value(with_session(variant, |session| {
+            session.find_backups()
+        })?)?),
+        ["doctor"] => Ok(to_value(doctor::run_doctor(&options.data_dir, variant)?)?),
+        ["asar", "ls", asar_path] => {
+            let asar = open_installed_asar(variant, asar_path)?;
+            let files = asar
+                .files()
+                .map(|path| {
+                    json!({
+                        "path": path,
+                        "size": asar.data(path).map(|data| data.len()).unwrap_or(0),
+                    })
+                })
+                .collect::<Vec<_>>();
+            Ok(Some(Value::Array(files)))
+        }
+        ["asar", "cat", asar_path, inner_path] => {
+            let asar = open_installed_asar(variant, asar_path)?;
+            let data = asar.read(&asar.resolve(inner_path)?)?;
+            io::stdout()
+                .write_all(&data)
+                .map_err(|_| WalError::IoError)?;
+            Ok(None)
+        }
+        ["asar", "extract", asar_path, output_dir] => {
+            let asar = open_installed_asar(variant, asar_path)?;
+            let mut extracted = 0;
+            for path in asar.files() {
+                let Some(data) = asar.data(path) else {
+                    continue;
+                };
+                let target = extract_target(Path::new(output_dir), path.as_str())?;
+                if let Some(parent) = target.parent() {
+                    fs::create_dir_all(parent).map_err(|_| WalError::IoError)?;
+                }
+                fs::write(&target, data).map_err(|_| WalError::IoError)?;
+                extracted += 1;
+            }
+            Ok(Some(json!({ "extracted": extracted })))
+        }
+        _ => Err(WalError::InvalidCommandError.into()),
+    }
+}
+
+/// 解出的文件必须落在 output_dir 之下：只接受普通的路径段，
+/// 盘符、根目录和 `..` 都会让 join 越出输出目录
+fn extract_target(output_dir: &Path, inner_path: &str) -> WalResult<PathBuf> {
+    let relative = Path::new(inner_path);
+    if !relative
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)))
+    {
+        return Err(WalError::AsarStructureError);
+    }
+    let target = output_dir.join(relative);
+    if !target.starts_with(output_dir) {
+        return Err(WalError::AsarStructureError);
+    }
+    Ok(target)
+}
+
+fn to_value<T: serde::Serialize>(value: T) -> WalResult<Option<Value>> {
+    serde_json::to_value(value)
+        .map(Some)
+        .map_err(|_| WalError::SerializationError)
+}
+
+fn status(variant: Option<&str>) -> WalResult<Value> {
+    let (id, executable, location) = lark_variant_session(variant, |helper| {
+        let location = helper.validate().ok().and_then(|_| helper.get_location());
+        Ok((
+            helper.variant().id.clone(),
+            helper.variant().executable.clone(),
+            location,
+        ))
+    })?;
+    let backups = location
+        .as_ref()
+        .and_then(|location| find_backups_recursively(&location.path).ok())
+        .unwrap_or_default();
+    Ok(json!({
+        "variant": id,
+        "location": location,
+        "running": is_process_running(&executable),
+        "backups": backups,
+    }))
+}
+
+/// 与窗口版相同的对账式应用：先还原到官方原版，再把主题合并后整体写入。
+/// 还原之前先在当前文件上试提交全部补丁，有补丁无法展开时直接报错，不改动安装目录
+fn apply(
+    data_dir: &Path,
+    variant: Option<&str>,
+    themes: &[&str],
+    dry_run: bool,
+) -> Result<Value, CliError> {
+    journal::recover_interrupted_applies(data_dir);
+    let sources = if themes.is_empty() {
+        enabled_theme_files(data_dir)?
+            .into_iter()
+            .map(|file_name| Ok((theme_store::read_theme(data_dir, &file_name)?, file_name)))
+            .collect::<WalResult<Vec<_>>>()?
+    } else {
+        themes
+            .iter()
+            .map(|theme| Ok((read_theme_arg(data_dir, theme)?, theme.to_string())))
+            .collect::<WalResult<Vec<_>>>()?
+    };
+    let themes = sources
+        .iter()
+        .map(|(content, label)| {
+            parse_theme(content).map_err(|error| CliError {
+                error,
+                context: json!({ "theme": label }),
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    // 报错时用主题的 id 指明出处，没有 id 时用文件名或命令行参数
+    let labels = themes
+        .iter()
+        .zip(&sources)
+        .map(|(theme, (_, label))| theme.id.clone().unwrap_or_else(|| label.clone()))
+        .collect::<Vec<_>>();
+    let merged = merge_themes(&themes);
+
+    let submit_all = |session: &mut LarkSession, dry_run: bool| -> Result<Vec<Value>, CliError> {
+        let mut plans = Vec::new();
+        for (asar_path, patches) in &merged.asar_patches {
+            for patch in patches {
+                let plan =
+                    submit_theme_patch(session, asar_path, patch, dry_run).map_err(|error| {
+                        CliError {
+                            error,
+                            context: json!({
+                                "themes": patch_sources(&themes, &labels, asar_path, patch),
+                                "asarPath": asar_path,
+                                "patch": patch.target,
+                            }),
+                        }
+                    })?;
+                plans.push(json!({ "asarPath": asar_path, "target": patch.target, "plan": plan }));
+            }
+        }
+        Ok(plans)
+    };
+
+    with_session(variant, |session| {
+        // 试运行不改动文件，直接在当前文件上展开
+        let plans = submit_all(session, true)?;
+        if dry_run {
+            return Ok(json!({ "themes": themes.len(), "plans": plans, "summary": null }));
+        }
+        session.restore_all_backups()?;
+        // 补丁要以还原后的原版为基准，重新提交一遍
+        let plans = submit_all(session, false)?;
+        if plans.is_empty() {
+            return Ok(json!({ "themes": themes.len(), "plans": plans, "summary": null }));
+        }
+        let token = CancellationToken::new();
+        let summary = session.apply_patches_with_progress(&token, &mut |event| {
+            if let Ok(line) = serde_json::to_string(&event) {
+                eprintln!("{}", line);
+            }
+        })?;
+        Ok(json!({ "themes": themes.len(), "plans": plans, "summary": summary }))
+    })
+}
+
+fn submit_theme_patch(
+    session: &mut LarkSession,
+    asar_path: &str,
+    patch: &ThemePatch,
+    dry_run: bool,
+) -> WalResult<PatchPlan> {
+    match &patch.target {
+        ThemePatchTarget::MainScript {
+            subject,
+            all_entries,
+        } => session.submit_main_script_patch(
+            asar_path,
+            subject,
+            &make_styles_script(patch),
+            None,
+            *all_entries,
+            dry_run,
+        ),
+        ThemePatchTarget::File { path } => {
+            session.submit_patch(asar_path, path, &make_styles_script(patch), None, dry_run)
+        }
+        ThemePatchTarget::Css { path } => {
+            session.submit_stylesheet_patch(asar_path, path, &make_stylesheet(patch), None, dry_run)
+        }
+        ThemePatchTarget::Html {
+            subject,
+            stylesheets,
+        } => session.submit_html_patch(
+            asar_path,
+            subject,
+            &make_html_injection(patch, stylesheets),
+            None,
+            dry_run,
+        ),
+    }
+}
+
+/// 合并后的补丁来自哪些主题：这些主题在同一 asar 上有相同目标的补丁
+fn patch_sources(
+    themes: &[WalTheme],
+    labels: &[String],
+    asar_path: &str,
+    patch: &ThemePatch,
+) -> Vec<String> {
+    let key = patch.target.key();
+    themes
+        .iter()
+        .zip(labels)
+        .filter(|(theme, _)| {
+            theme
+                .asar_patches
+                .get(asar_path)
+                .is_some_and(|patches| patches.iter().any(|p| p.target.key() == key))
+        })
+        .map(|(_, label)| label.clone())
+        .collect()
+}
+
+/// 主题库中启用的主题文件名，按清单顺序（顶部优先级最高）
+fn enabled_theme_files(data_dir: &Path) -> WalResult<Vec<String>> {
+    let manifest = serde_json::from_str::<Value>(&theme_store::read_manifest(data_dir)?)
+        .map_err(|_| WalError::SerializationError)?;
+    Ok(manifest["themes"]
+        .as_array()
+        .map(|themes| {
+            themes
+                .iter()
+                .filter(|theme| theme["enabled"].as_bool().unwrap_or(false))
+                .filter_map(|theme| theme["fileName"].as_str().map(|name| name.to_string()))
+                .collect()
+        })
+        .unwrap_or_default())
+}
+
+/// 参数是存在的文件时直接读取，否则按主题库中的 id 查找
+fn read_theme_arg(data_dir: &Path, theme: &str) -> WalResult<String> {
+    if Path::new(theme).is_file() {
+        return fs::read_to_string(theme).map_err(|_| WalError::IoError);
+    }
+    let manifest = serde_json::from_str::<Value>(&theme_store::read_manifest(data_dir)?)
+        .map_err(|_| WalError::SerializationError)?;
+    let file_name = manifest["themes"]
+        .as_array()
+        .and_then(|themes| themes.iter().find(|entry| entry["id"] == theme))
+        .and_then(|entry| entry["fileName"].as_str())
+        .ok_or(WalError::InvalidCommandError)?;
+    theme_store::read_theme(data_dir, file_name)
+}
+
+/// 新建会话执行操作，结束后关闭，避免残留在会话表中
+fn with_session<T, E: From<WalError>>(
+    variant: Option<&str>,
+    f: impl FnOnce(&mut LarkSession) -> Result<T, E>,
+) -> Result<T, E> {
+    let session = session::create_lark_session(variant)?;
+    let mut session = session.lock().map_err(|_| WalError::LockError)?;
+    let id = session.id().to_string();
+    let result = f(&mut session);
+    drop(session);
+    let _ = session::close_lark_session(&id);
+    result
+}
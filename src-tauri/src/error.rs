@@ -9,6 +9,32 @@ pub enum WalError {
     SessionNotFoundError,
     InvalidCommandError,
     AlreadyPatchedError,
+    LarkNotFoundError,
+    InvalidInstallDirError,
+    SerializationError,
+    UnknownVariantError,
+    NoQueuedPatchesError,
+    ApplyInProgressError,
+    /// 安装目录正被其他会话或进程修改，值为占用者描述
+    BusyError(String),
+    /// 写入前检查发现阻塞问题，详情通过 preflight 命令获取
+    PreflightError,
+    /// 搜索用的正则表达式无效
+    InvalidPatternError,
+    /// subject 目录下没有 index.html，或其中没有能在 asar 内找到的脚本
+    MainScriptNotFoundError,
+    /// 补丁或读取的内部路径在 asar 中不存在
+    TargetNotFoundError,
+    /// 自定义客户端的 id 与内置客户端相同或彼此重复
+    ReservedVariantIdError,
+    /// 排队的补丁是在另一份 asar 上算出来的（客户端已更新或文件被改动），值为 asar 路径
+    StaleQueueError(String),
+    /// 应用时处理某个 asar 的工作线程 panic
+    WorkerPanicError,
+    /// 应用被用户取消，或因其他 asar 失败而中止
+    ApplyCancelledError,
+    /// 传入的路径越出了安装目录
+    PathOutsideInstallError,
 }
 
 impl From<windows_result::Error> for WalError {
@@ -20,5 +46,19 @@ impl From<windows_result::Error> for WalError {
 pub type WalResult<T, R = WalError> = Result<T, R>;
 
 pub fn make_tauri_result<T>(result: WalResult<T>) -> tauri::Result<T> {
-    result.map_err(|_| tauri::Error::InvokeKey)
+    result.map_err(to_tauri_error)
+}
+
+/// 多数错误前端只需知道失败；占用冲突要带上占用者、过期的队列要带上 asar，便于提示用户
+pub fn to_tauri_error(error: WalError) -> tauri::Error {
+    match error {
+        WalError::BusyError(holder) => {
+            tauri::Error::Io(std::io::Error::other(format!("busy: {}", holder)))
+        }
+        WalError::StaleQueueError(asar_path) => tauri::Error::Io(std::io::Error::other(format!(
+            "stale queue: {} changed after patches were submitted",
+            asar_path
+        ))),
+        _ => tauri::Error::InvokeKey,
+    }
 }
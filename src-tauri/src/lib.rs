@@ -1,11 +1,32 @@
-use tauri::{AppHandle, Emitter, Manager};
+use std::sync::Arc;
+
+use tauri::{ipc::Channel, AppHandle, Emitter, Manager};
 
 use crate::{
-    error::make_tauri_result,
-    shared::lark_helper_session,
+    error::{make_tauri_result, to_tauri_error, WalError, WalResult},
+    shared::{get_lark_helpers, lark_variant_session, set_app_data_dir, set_custom_lark_variants},
     utils::{
-        lark::{find::LarkFinder, wrappers::session},
-        log::logger,
+        doctor::{
+            self,
+            bundle::{self, BundleOptions},
+            DoctorReport,
+        },
+        file::journal::{self, RecoveredApply},
+        lark::{
+            explore::{self, AsarSearchMatch, AsarSearchQuery, AsarTreeNode},
+            find::{self, LarkFinder},
+            lark::{LarkLocation, LarkPathSubscriber},
+            variant::{LarkVariant, DEFAULT_VARIANT_ID},
+            wrappers::{
+                apply::{self, ApplyEvent, ApplySummary},
+                session,
+            },
+        },
+        log::{
+            crash::{self, CrashReport},
+            file::RotatingFile,
+            logger::{self, LogLevel, LogRecord},
+        },
         platform::{
             os::join_components,
             process::{
@@ -13,7 +34,7 @@ use crate::{
                 wait_until_all_processes_ended,
             },
         },
-        theme_store,
+        settings, theme_store,
     },
 };
 
@@ -21,39 +42,171 @@ pub mod error;
 pub mod shared;
 pub mod utils;
 
+fn variant_executable(variant: Option<&str>) -> tauri::Result<String> {
+    make_tauri_result(lark_variant_session(variant, |helper| {
+        Ok(helper.variant().executable.clone())
+    }))
+}
+
 #[tauri::command]
-fn is_lark_running() -> bool {
-    is_process_running("Feishu.exe")
+fn is_lark_running(variant: Option<String>) -> tauri::Result<bool> {
+    let executable = variant_executable(variant.as_deref())?;
+    Ok(is_process_running(&executable))
 }
 
 #[tauri::command]
-fn kill_lark() -> bool {
-    kill_all_processes("Feishu.exe")
+fn kill_lark(variant: Option<String>) -> tauri::Result<bool> {
+    let executable = variant_executable(variant.as_deref())?;
+    Ok(kill_all_processes(&executable))
 }
 
 #[tauri::command]
-async fn wait_until_lark_ended() {
-    wait_until_all_processes_ended("Feishu.exe").await;
+async fn wait_until_lark_ended(variant: Option<String>) -> tauri::Result<()> {
+    let executable = variant_executable(variant.as_deref())?;
+    wait_until_all_processes_ended(&executable).await;
+    Ok(())
 }
 
 #[tauri::command]
-fn launch_lark() -> () {
-    let _ = lark_helper_session(|helper| {
+fn launch_lark(variant: Option<String>) -> () {
+    let _ = lark_variant_session(variant.as_deref(), |helper| {
         let path = helper.locate()?;
-        let lark_path = join_components(&[&path, "Feishu.exe"])?;
+        let lark_path = join_components(&[&path, &helper.variant().executable])?;
         let _ = launch_process(&lark_path);
         Ok(())
     });
 }
 
 #[tauri::command]
-fn get_lark_base_path() -> tauri::Result<String> {
-    make_tauri_result(lark_helper_session(|helper| helper.locate()))
+fn get_lark_base_path(variant: Option<String>) -> tauri::Result<String> {
+    make_tauri_result(lark_variant_session(variant.as_deref(), |helper| {
+        helper.locate()
+    }))
+}
+
+/// 丢弃缓存的飞书路径并重新查找（飞书更新或重装到别处之后使用）
+#[tauri::command]
+fn refresh_lark_base_path(variant: Option<String>) -> tauri::Result<String> {
+    make_tauri_result(lark_variant_session(variant.as_deref(), |helper| {
+        helper.refresh()
+    }))
+}
+
+/// 当前解析出的飞书位置，包含其来源（手动指定 / 注册表 / 常见目录）
+#[tauri::command]
+fn get_lark_location(variant: Option<String>) -> tauri::Result<LarkLocation> {
+    make_tauri_result(lark_variant_session(variant.as_deref(), |helper| {
+        helper.locate()?;
+        helper.get_location().ok_or(WalError::LarkNotFoundError)
+    }))
+}
+
+/// 所有已知客户端（内置 + 自定义）的描述
+#[tauri::command]
+fn get_lark_variants() -> tauri::Result<Vec<LarkVariant>> {
+    let helpers = get_lark_helpers().map_err(|_| tauri::Error::InvokeKey)?;
+    Ok(helpers
+        .iter()
+        .map(|helper| helper.variant().clone())
+        .collect())
+}
+
+/// 检测每种客户端是否安装、装在哪里、是否正在运行
+#[tauri::command]
+fn detect_lark_variants() -> tauri::Result<Vec<find::DetectedLarkVariant>> {
+    let mut helpers = get_lark_helpers().map_err(|_| tauri::Error::InvokeKey)?;
+    Ok(find::detect_variants(&mut helpers))
+}
+
+/// 保存自定义客户端描述（私有化部署等），保存后立即生效；列表即全部自定义客户端
+#[tauri::command]
+fn save_custom_lark_variants(app: AppHandle, variants: Vec<LarkVariant>) -> tauri::Result<()> {
+    let dir = app_data_dir(&app)?;
+    let mut settings = settings::read_settings(&dir).map_err(|_| tauri::Error::InvokeKey)?;
+    // 先生效再保存，id 冲突时不写入设置
+    make_tauri_result(set_custom_lark_variants(variants.clone()))?;
+    settings.custom_variants = variants;
+    settings::write_settings(&dir, &settings).map_err(|_| tauri::Error::InvokeKey)
+}
+
+#[tauri::command]
+fn validate_lark_install_directory(
+    variant: Option<String>,
+    path: String,
+) -> tauri::Result<find::LarkInstallValidation> {
+    make_tauri_result(lark_variant_session(variant.as_deref(), |helper| {
+        Ok(find::validate_install_dir(helper.variant(), &path))
+    }))
 }
 
+/// 弹出目录选择框并校验选中的目录；取消时返回 null
 #[tauri::command]
-fn create_lark_session() -> tauri::Result<String> {
-    let session = session::create_lark_session().map_err(|_| tauri::Error::InvokeKey)?;
+async fn pick_lark_install_directory(
+    app: AppHandle,
+    variant: Option<String>,
+) -> tauri::Result<Option<find::LarkInstallValidation>> {
+    use tauri_plugin_dialog::DialogExt;
+
+    let Some(dir) = app.dialog().file().blocking_pick_folder() else {
+        return Ok(None);
+    };
+    let dir = dir.into_path().map_err(|_| tauri::Error::InvokeKey)?;
+    let dir = dir.to_str().ok_or(tauri::Error::InvokeKey)?;
+    validate_lark_install_directory(variant, dir.to_string()).map(Some)
+}
+
+#[tauri::command]
+fn get_lark_path_override(
+    app: AppHandle,
+    variant: Option<String>,
+) -> tauri::Result<Option<String>> {
+    let dir = app_data_dir(&app)?;
+    let settings = settings::read_settings(&dir).map_err(|_| tauri::Error::InvokeKey)?;
+    let variant = variant.as_deref().unwrap_or(DEFAULT_VARIANT_ID);
+    Ok(settings.lark_path_overrides.get(variant).cloned())
+}
+
+/// 保存手动指定的安装目录（传 null 清除）并立即重新查找；目录无效时拒绝保存
+#[tauri::command]
+fn set_lark_path_override(
+    app: AppHandle,
+    variant: Option<String>,
+    path: Option<String>,
+) -> tauri::Result<String> {
+    if let Some(path) = &path {
+        if !validate_lark_install_directory(variant.clone(), path.clone())?.valid {
+            return Err(tauri::Error::InvokeKey);
+        }
+    }
+    let dir = app_data_dir(&app)?;
+    let mut settings = settings::read_settings(&dir).map_err(|_| tauri::Error::InvokeKey)?;
+    let variant_id = variant.as_deref().unwrap_or(DEFAULT_VARIANT_ID).to_string();
+    match &path {
+        Some(path) => settings
+            .lark_path_overrides
+            .insert(variant_id, path.clone()),
+        None => settings.lark_path_overrides.remove(&variant_id),
+    };
+    settings::write_settings(&dir, &settings).map_err(|_| tauri::Error::InvokeKey)?;
+    make_tauri_result(lark_variant_session(variant.as_deref(), |helper| {
+        helper.set_path_override(path);
+        helper.refresh()
+    }))
+}
+
+#[tauri::command]
+fn create_lark_session(variant: Option<String>) -> tauri::Result<String> {
+    let session =
+        session::create_lark_session(variant.as_deref()).map_err(|_| tauri::Error::InvokeKey)?;
+    let session = session.lock().map_err(|_| tauri::Error::InvokeKey)?;
+    Ok(session.id().to_owned())
+}
+
+/// 创建绑定到指定目录的会话，提交、应用、备份、还原都只作用于该目录
+#[tauri::command]
+fn create_lark_directory_session(base_path: String) -> tauri::Result<String> {
+    let session =
+        session::create_lark_directory_session(&base_path).map_err(|_| tauri::Error::InvokeKey)?;
     let session = session.lock().map_err(|_| tauri::Error::InvokeKey)?;
     Ok(session.id().to_owned())
 }
@@ -64,11 +217,161 @@ fn close_lark_session(id: String) -> tauri::Result<()> {
     Ok(())
 }
 
+/// 列出现存的会话及其持有的内容（会先回收过期会话）
+#[tauri::command]
+fn list_lark_sessions() -> tauri::Result<Vec<session::SessionInfo>> {
+    make_tauri_result(session::list_lark_sessions())
+}
+
 #[tauri::command]
 fn invoke_lark_session(id: String, command: String, args: Vec<String>) -> tauri::Result<String> {
+    session::interpret_command(&id, &command, args).map_err(to_tauri_error)
+}
+
+/// 在后台任务中应用会话排队的补丁，通过 Channel 推送每个 asar 的阶段与字节进度，
+/// 结束时推送并返回汇总。可用 cancel_lark_apply 取消。
+#[tauri::command]
+async fn apply_lark_session(
+    id: String,
+    on_progress: Channel<ApplyEvent>,
+) -> tauri::Result<ApplySummary> {
+    let token = apply::begin_apply(&id).map_err(|_| tauri::Error::InvokeKey)?;
+    let task_id = id.clone();
+    let channel = on_progress.clone();
+    let result = tauri::async_runtime::spawn_blocking(move || {
+        let session = session::get_lark_session(&task_id).ok_or(WalError::SessionNotFoundError)?;
+        let mut session = session.lock().map_err(|_| WalError::LockError)?;
+        session.touch();
+        session.apply_patches_with_progress(&token, &mut |event| {
+            let _ = channel.send(event);
+        })
+    })
+    .await;
+    apply::finish_apply(&id);
+
+    match result? {
+        Ok(summary) => {
+            let _ = on_progress.send(ApplyEvent::Finished(summary.clone()));
+            Ok(summary)
+        }
+        Err(e) => {
+            let _ = on_progress.send(ApplyEvent::Failed {
+                message: format!("{:?}", e),
+            });
+            Err(to_tauri_error(e))
+        }
+    }
+}
+
+/// 取消正在进行的应用；没有进行中的应用时返回 false
+#[tauri::command]
+fn cancel_lark_apply(id: String) -> tauri::Result<bool> {
+    make_tauri_result(apply::cancel_apply(&id))
+}
+
+/// 已安装客户端中某个 asar 的完整目录树，含大小与解包、链接等标记
+#[tauri::command]
+async fn list_asar_tree(variant: Option<String>, asar_path: String) -> tauri::Result<AsarTreeNode> {
+    let result = tauri::async_runtime::spawn_blocking(move || -> WalResult<AsarTreeNode> {
+        let file = explore::open_installed_asar(variant.as_deref(), &asar_path)?;
+        Ok(explore::asar_tree(&file))
+    })
+    .await?;
+    make_tauri_result(result)
+}
+
+#[tauri::command]
+async fn read_asar_text(
+    variant: Option<String>,
+    asar_path: String,
+    inner_path: String,
+) -> tauri::Result<String> {
+    let result = tauri::async_runtime::spawn_blocking(move || -> WalResult<String> {
+        let file = explore::open_installed_asar(variant.as_deref(), &asar_path)?;
+        explore::read_text(&file, &inner_path)
+    })
+    .await?;
+    make_tauri_result(result)
+}
+
+/// 按子串或正则搜索 asar 内的文本文件
+#[tauri::command]
+async fn search_asar(
+    variant: Option<String>,
+    asar_path: String,
+    query: AsarSearchQuery,
+) -> tauri::Result<Vec<AsarSearchMatch>> {
     let result =
-        session::interpret_command(&id, &command, args).map_err(|_| tauri::Error::InvokeKey)?;
-    Ok(result)
+        tauri::async_runtime::spawn_blocking(move || -> WalResult<Vec<AsarSearchMatch>> {
+            let file = explore::open_installed_asar(variant.as_deref(), &asar_path)?;
+            explore::search(&file, &query)
+        })
+        .await?;
+    make_tauri_result(result)
+}
+
+/// 诊断安装、asar、备份、主题库与进程状态
+#[tauri::command]
+async fn run_lark_doctor(app: AppHandle, variant: Option<String>) -> tauri::Result<DoctorReport> {
+    let dir = app_data_dir(&app)?;
+    // 要逐个映射并扫描 asar，放到阻塞线程里
+    let result = tauri::async_runtime::spawn_blocking(move || -> WalResult<DoctorReport> {
+        doctor::run_doctor(&dir, variant.as_deref())
+    })
+    .await?;
+    make_tauri_result(result)
+}
+
+/// 把诊断报告、日志、主题库、备份清单、版本信息和 asar 头部导出为一个 zip。
+/// logs 为前端日志面板中的最近日志；redact_scripts 为真时隐去主题中的脚本、自定义样式和样式表地址，并把路径中的用户目录改写为 %USERPROFILE%。
+#[tauri::command]
+async fn export_diagnostic_bundle(
+    app: AppHandle,
+    variant: Option<String>,
+    output_path: String,
+    logs: Vec<String>,
+    redact_scripts: bool,
+) -> tauri::Result<DoctorReport> {
+    let dir = app_data_dir(&app)?;
+    let options = BundleOptions {
+        logs,
+        redact_scripts,
+    };
+    let result = tauri::async_runtime::spawn_blocking(move || -> WalResult<DoctorReport> {
+        bundle::export_bundle(&dir, variant.as_deref(), &output_path, &options)
+    })
+    .await?;
+    make_tauri_result(result)
+}
+
+/// 弹出保存对话框，选择诊断包的位置；取消时返回 null
+#[tauri::command]
+async fn pick_diagnostic_bundle_path(app: AppHandle) -> tauri::Result<Option<String>> {
+    use tauri_plugin_dialog::DialogExt;
+
+    let Some(file_path) = app
+        .dialog()
+        .file()
+        .add_filter("zip", &["zip"])
+        .set_file_name("wal-diagnostics.zip")
+        .blocking_save_file()
+    else {
+        return Ok(None);
+    };
+    let path = file_path.into_path().map_err(|_| tauri::Error::InvokeKey)?;
+    Ok(path.to_str().map(|path| path.to_string()))
+}
+
+/// 启动时从应用日志中恢复的、上次被中断的应用
+#[tauri::command]
+fn get_recovered_applies() -> tauri::Result<Vec<RecoveredApply>> {
+    make_tauri_result(journal::recovered_applies())
+}
+
+/// 上次运行崩溃时的记录；没有崩溃时为 null
+#[tauri::command]
+fn get_previous_crash() -> tauri::Result<Option<CrashReport>> {
+    make_tauri_result(crash::previous_crash())
 }
 
 fn app_data_dir(app: &AppHandle) -> tauri::Result<std::path::PathBuf> {
@@ -108,6 +411,25 @@ async fn pick_theme_file(app: AppHandle) -> tauri::Result<Option<PickedThemeDto>
     }))
 }
 
+/// 弹出保存对话框，选择导出 asar 的位置；取消时返回 null
+#[tauri::command]
+async fn pick_asar_export_path(
+    app: AppHandle,
+    file_name: Option<String>,
+) -> tauri::Result<Option<String>> {
+    use tauri_plugin_dialog::DialogExt;
+
+    let mut dialog = app.dialog().file().add_filter("asar", &["asar"]);
+    if let Some(file_name) = file_name {
+        dialog = dialog.set_file_name(file_name);
+    }
+    let Some(file_path) = dialog.blocking_save_file() else {
+        return Ok(None);
+    };
+    let path = file_path.into_path().map_err(|_| tauri::Error::InvokeKey)?;
+    Ok(path.to_str().map(|path| path.to_string()))
+}
+
 /// 按 id 派生文件名写入托管目录（同名覆盖），返回实际文件名。
 #[tauri::command]
 fn save_theme(app: AppHandle, id: String, content: String) -> tauri::Result<String> {
@@ -141,21 +463,113 @@ fn write_theme_manifest(app: AppHandle, content: String) -> tauri::Result<()> {
 }
 
 #[tauri::command]
-fn open_lark_install_directory() -> tauri::Result<()> {
-    let path = get_lark_base_path().map_err(|_| tauri::Error::InvokeKey)?;
+fn open_lark_install_directory(variant: Option<String>) -> tauri::Result<()> {
+    let path = get_lark_base_path(variant).map_err(|_| tauri::Error::InvokeKey)?;
     let _ = tokio::process::Command::new("explorer.exe")
         .arg(path.as_str())
         .spawn();
     Ok(())
 }
 
+/// 序号大于 since 的日志，供日志面板重新打开或页面刷新后补齐
+#[tauri::command]
+fn get_log_history(since: Option<u64>) -> tauri::Result<Vec<LogRecord>> {
+    make_tauri_result(logger::log_history(since.unwrap_or(0)))
+}
+
+#[tauri::command]
+fn get_log_level() -> LogLevel {
+    logger::get_log_level()
+}
+
+/// 立即生效，并写入设置供下次启动使用
+#[tauri::command]
+fn set_log_level(app: AppHandle, level: LogLevel) -> tauri::Result<()> {
+    logger::set_log_level(level);
+    let dir = app_data_dir(&app)?;
+    let mut settings = settings::read_settings(&dir).map_err(|_| tauri::Error::InvokeKey)?;
+    settings.log_level = Some(level);
+    settings::write_settings(&dir, &settings).map_err(|_| tauri::Error::InvokeKey)
+}
+
 fn subscribe_log(app: &AppHandle) {
     let app_handle = tauri::AppHandle::clone(app);
-    let _ = logger::subscribe_log(Box::new(move |message| {
-        let _ = app_handle.emit("log-events", format!("[NATIVE] {}", message));
+    let _ = logger::subscribe_log(Box::new(move |record: &LogRecord| {
+        let _ = app_handle.emit("log-events", record);
     }));
 }
 
+/// 记录应用数据目录，并开始把日志写入 <app_data>/logs
+fn init_app_data_dir(app: &AppHandle) {
+    let Ok(dir) = app_data_dir(app) else {
+        return;
+    };
+    set_app_data_dir(dir.clone());
+    if let Ok(file) = RotatingFile::open(&dir) {
+        let _ = logger::set_log_file(file);
+    }
+    if let Some(report) = crash::take_previous_crash(&dir) {
+        warn!("previous run crashed: {}", report.message);
+    }
+}
+
+/// panic 时写入崩溃文件并通知前端，下次启动时据此提示
+fn install_panic_hook(app: &AppHandle) {
+    let Ok(dir) = app_data_dir(app) else {
+        return;
+    };
+    let app_handle = tauri::AppHandle::clone(app);
+    crash::install_panic_hook(
+        dir,
+        Box::new(move |report| {
+            let _ = app_handle.emit("native-panic", report);
+        }),
+    );
+}
+
+/// 定期回收空闲会话，避免前端忘记关闭时 asar 数据一直留在内存里
+fn spawn_session_collector() {
+    tauri::async_runtime::spawn(async {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(60));
+        loop {
+            interval.tick().await;
+            let _ = session::collect_garbage();
+        }
+    });
+}
+
+fn load_settings(app: &AppHandle) {
+    let Ok(dir) = app_data_dir(app) else {
+        return;
+    };
+    if let Ok(settings) = settings::read_settings(&dir) {
+        settings::apply_settings(settings);
+    }
+}
+
+/// 补完或回退上次中断的应用，并通知前端
+fn recover_interrupted_applies(app: &AppHandle) {
+    let Ok(dir) = app_data_dir(app) else {
+        return;
+    };
+    let recovered = journal::recover_interrupted_applies(&dir);
+    if !recovered.is_empty() {
+        let _ = app.emit("apply-recovered", recovered);
+    }
+}
+
+fn subscribe_lark_path_change(app: &AppHandle) {
+    let app_handle = tauri::AppHandle::clone(app);
+    let subscriber: LarkPathSubscriber = Arc::new(move |change| {
+        let _ = app_handle.emit("lark-path-changed", change.clone());
+    });
+    if let Ok(mut helpers) = get_lark_helpers() {
+        helpers
+            .iter_mut()
+            .for_each(|helper| helper.subscribe_path_change(subscriber.clone()));
+    }
+}
+
 #[cfg(debug_assertions)]
 fn setup_debug(app: &mut tauri::App) {
     let windows_map = app.webview_windows();
@@ -185,7 +599,13 @@ fn setup(app: &mut tauri::App) -> Result<(), Box<dyn std::error::Error>> {
             );
         });
     }
+    init_app_data_dir(app.handle());
+    install_panic_hook(app.handle());
     subscribe_log(app.handle());
+    subscribe_lark_path_change(app.handle());
+    load_settings(app.handle());
+    recover_interrupted_applies(app.handle());
+    spawn_session_collector();
     Ok(())
 }
 
@@ -199,15 +619,40 @@ pub fn run() {
         .setup(setup)
         .invoke_handler(tauri::generate_handler![
             get_lark_base_path,
+            refresh_lark_base_path,
+            get_lark_location,
+            get_lark_variants,
+            detect_lark_variants,
+            save_custom_lark_variants,
+            validate_lark_install_directory,
+            pick_lark_install_directory,
+            get_lark_path_override,
+            set_lark_path_override,
             is_lark_running,
             kill_lark,
             launch_lark,
             wait_until_lark_ended,
             create_lark_session,
+            create_lark_directory_session,
             invoke_lark_session,
+            apply_lark_session,
+            cancel_lark_apply,
+            get_recovered_applies,
+            get_previous_crash,
+            run_lark_doctor,
+            export_diagnostic_bundle,
+            pick_diagnostic_bundle_path,
+            get_log_history,
+            list_asar_tree,
+            read_asar_text,
+            search_asar,
+            get_log_level,
+            set_log_level,
             close_lark_session,
+            list_lark_sessions,
             open_lark_install_directory,
             pick_theme_file,
+            pick_asar_export_path,
             save_theme,
             read_theme,
             delete_theme,
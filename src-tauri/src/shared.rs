@@ -1,23 +1,94 @@
-use std::sync::{LazyLock, Mutex, MutexGuard};
+use std::{
+    path::{Path, PathBuf},
+    sync::{LazyLock, Mutex, MutexGuard, OnceLock},
+};
 
 use crate::{
     error::{WalError, WalResult},
-    utils::lark::lark::Lark,
+    utils::lark::{
+        lark::Lark,
+        variant::{builtin_variants, LarkVariant, DEFAULT_VARIANT_ID},
+    },
 };
 
-pub static LARK_HELPER: LazyLock<Mutex<Lark>> = LazyLock::new(|| Mutex::new(Lark::new()));
+/// 每种客户端一个 Lark，各自缓存自己的安装位置
+pub static LARK_HELPERS: LazyLock<Mutex<Vec<Lark>>> =
+    LazyLock::new(|| Mutex::new(builtin_variants().into_iter().map(Lark::new).collect()));
+
+/// 应用数据目录，启动时设置；未设置时不写应用日志等持久化数据
+static APP_DATA_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+pub fn set_app_data_dir(dir: PathBuf) {
+    let _ = APP_DATA_DIR.set(dir);
+}
+
+pub fn get_app_data_dir() -> Option<&'static Path> {
+    APP_DATA_DIR.get().map(|dir| dir.as_path())
+}
 
 pub trait IntoLarkSessionResult<T> {
     fn into_lark_session_result(self) -> WalResult<T>;
 }
 
-pub fn get_lark_helper<'a>() -> WalResult<MutexGuard<'a, Lark>> {
-    LARK_HELPER.lock().map_err(|_| WalError::LockError)
+pub fn get_lark_helpers<'a>() -> WalResult<MutexGuard<'a, Vec<Lark>>> {
+    LARK_HELPERS.lock().map_err(|_| WalError::LockError)
+}
+
+/// 在指定客户端上执行操作；variant 为 None 时使用默认客户端（飞书）
+pub fn lark_variant_session<T>(
+    variant: Option<&str>,
+    f: impl FnOnce(&mut Lark) -> WalResult<T>,
+) -> WalResult<T> {
+    let mut helpers = get_lark_helpers()?;
+    let id = variant.unwrap_or(DEFAULT_VARIANT_ID);
+    let helper = helpers
+        .iter_mut()
+        .find(|helper| helper.variant().id == id)
+        .ok_or(WalError::UnknownVariantError)?;
+    f(helper)
 }
 
 pub fn lark_helper_session<T>(f: impl FnOnce(&mut Lark) -> WalResult<T>) -> WalResult<T> {
-    match get_lark_helper() {
-        Ok(mut helper) => f(&mut helper),
-        Err(e) => Err(e),
+    lark_variant_session(None, f)
+}
+
+/// 用 variants 替换全部自定义客户端：已有的就地更新，保留手动指定的目录与订阅者；
+/// 不在列表中的自定义客户端移除。id 与内置客户端相同或重复时整体拒绝
+pub fn set_custom_lark_variants(variants: Vec<LarkVariant>) -> WalResult<()> {
+    let builtin_ids = builtin_variants()
+        .into_iter()
+        .map(|variant| variant.id)
+        .collect::<Vec<_>>();
+    for (index, variant) in variants.iter().enumerate() {
+        if builtin_ids.contains(&variant.id) || variants[..index].iter().any(|v| v.id == variant.id)
+        {
+            return Err(WalError::ReservedVariantIdError);
+        }
+    }
+
+    let mut helpers = get_lark_helpers()?;
+    let subscribers = helpers
+        .first()
+        .map(|helper| helper.path_subscribers())
+        .unwrap_or_default();
+    helpers.retain(|helper| {
+        builtin_ids.contains(&helper.variant().id)
+            || variants.iter().any(|v| v.id == helper.variant().id)
+    });
+    for variant in variants {
+        match helpers
+            .iter_mut()
+            .find(|helper| helper.variant().id == variant.id)
+        {
+            Some(helper) => helper.set_variant(variant),
+            None => {
+                let mut helper = Lark::new(variant);
+                subscribers
+                    .iter()
+                    .for_each(|subscriber| helper.subscribe_path_change(subscriber.clone()));
+                helpers.push(helper);
+            }
+        }
     }
+    Ok(())
 }
@@ -0,0 +1,256 @@
+use std::{borrow::Cow, env, fs, io::Write, path::Path};
+
+use regex::Regex;
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipWriter};
+
+use crate::{
+    error::{WalError, WalResult},
+    info,
+    shared::lark_variant_session,
+    utils::{
+        doctor::{run_doctor, DoctorReport},
+        file::backup::{find_backups_recursively, reverse_make_backup_path},
+        lark::{file::LarkAsarFile, find::validate_install_dir},
+        log::{crash::last_crash_file, file::log_files},
+        theme_store,
+    },
+};
+
+/// 脱敏后替换脚本、样式和地址的占位
+const REDACTED: &str = "<redacted>";
+/// 内容可能涉及用户隐私的主题字段
+const REDACTED_KEYS: [&str; 2] = ["customScript", "customCss"];
+/// 脱敏后替换用户目录的占位
+const USER_PROFILE: &str = "%USERPROFILE%";
+
+#[derive(Clone, Debug, Default)]
+pub struct BundleOptions {
+    /// 前端日志面板中的最近日志，包含原生端转发的日志
+    pub logs: Vec<String>,
+    /// 把主题中的 customScript、customCss 和 html 补丁引入的样式表地址替换为占位，
+    /// 并把各文件中用户目录开头的路径改写为 %USERPROFILE%
+    pub redact_scripts: bool,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct BackupInventoryItem {
+    backup_path: String,
+    original_path: String,
+    backup_size: Option<u64>,
+    original_exists: bool,
+}
+
+/// 把诊断报告、日志、主题库、备份清单、版本信息和 asar 头部打包成一个 zip。
+/// 只收集排查需要的内容，不含聊天记录等用户数据。
+pub fn export_bundle(
+    app_data_dir: &Path,
+    variant: Option<&str>,
+    output_path: &str,
+    options: &BundleOptions,
+) -> WalResult<DoctorReport> {
+    info!("exporting diagnostic bundle to {}", output_path);
+    let report = run_doctor(app_data_dir, variant)?;
+
+    let file = fs::File::create(output_path).map_err(|_| WalError::IoError)?;
+    let mut zip = ZipWriter::new(file);
+    let file_options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    // 路径里带着用户名，报告、清单和日志都要改写
+    let profile = options
+        .redact_scripts
+        .then(|| env::var("USERPROFILE").ok())
+        .flatten()
+        .and_then(|profile| user_profile_pattern(&profile));
+    let mut add = |name: &str, content: &[u8]| -> WalResult<()> {
+        let content = match &profile {
+            Some(profile) => Cow::Owned(
+                profile
+                    .replace_all(&String::from_utf8_lossy(content), USER_PROFILE)
+                    .into_owned()
+                    .into_bytes(),
+            ),
+            None => Cow::Borrowed(content),
+        };
+        zip.start_file(name, file_options)
+            .map_err(|_| WalError::IoError)?;
+        zip.write_all(&content).map_err(|_| WalError::IoError)
+    };
+
+    add("doctor.json", &to_json(&report)?)?;
+    add("logs.txt", options.logs.join("\n").as_bytes())?;
+    for path in log_files(app_data_dir) {
+        let Ok(content) = fs::read(&path) else {
+            continue;
+        };
+        let name = format!(
+            "logs/{}",
+            path.file_name().unwrap_or_default().to_string_lossy()
+        );
+        add(&name, &content)?;
+    }
+    if let Ok(content) = fs::read(last_crash_file(app_data_dir)) {
+        add("crash.json", &content)?;
+    }
+    add(
+        "themes.json",
+        theme_store::read_manifest(app_data_dir)?.as_bytes(),
+    )?;
+    if let Ok(entries) = fs::read_dir(theme_store::themes_dir(app_data_dir)) {
+        for entry in entries.flatten() {
+            let Ok(content) = fs::read_to_string(entry.path()) else {
+                continue;
+            };
+            let content = if options.redact_scripts {
+                redact_scripts(&content)
+            } else {
+                content
+            };
+            let name = format!("themes/{}", entry.file_name().to_string_lossy());
+            add(&name, content.as_bytes())?;
+        }
+    }
+
+    if let Some(location) = &report.location {
+        add("lark.json", &to_json(location)?)?;
+
+        let backups = find_backups_recursively(&location.path).unwrap_or_default();
+        let inventory = backups
+            .iter()
+            .map(|backup| {
+                let original_path = reverse_make_backup_path(backup);
+                BackupInventoryItem {
+                    backup_path: backup.clone(),
+                    backup_size: fs::metadata(backup).map(|m| m.len()).ok(),
+                    original_exists: Path::new(&original_path).is_file(),
+                    original_path,
+                }
+            })
+            .collect::<Vec<_>>();
+        add("backups.json", &to_json(&inventory)?)?;
+
+        let variant_info = lark_variant_session(variant, |helper| Ok(helper.variant().clone()))?;
+        let validation = validate_install_dir(&variant_info, &location.install_dir);
+        for asar_path in &validation.asar_files {
+            let Ok(asar) = LarkAsarFile::new(location.path.clone(), asar_path.clone()) else {
+                continue;
+            };
+            let name = format!("headers/{}.json", asar_path.replace(['\\', '/'], "_"));
+            add(&name, &to_json(asar.header().json())?)?;
+        }
+    }
+
+    zip.finish().map_err(|_| WalError::IoError)?;
+    Ok(report)
+}
+
+fn to_json<T: serde::Serialize>(value: &T) -> WalResult<Vec<u8>> {
+    serde_json::to_vec_pretty(value).map_err(|_| WalError::SerializationError)
+}
+
+/// 匹配用户目录开头的路径。分隔符可能是 `\`、`/`，或 JSON 与调试输出中转义后的 `\\`；
+/// Windows 路径不区分大小写
+fn user_profile_pattern(profile: &str) -> Option<Regex> {
+    let pattern = profile
+        .split(['\\', '/'])
+        .filter(|segment| !segment.is_empty())
+        .map(regex::escape)
+        .collect::<Vec<_>>()
+        .join(r"(?:\\\\|\\|/)");
+    if pattern.is_empty() {
+        return None;
+    }
+    Regex::new(&format!(r"(?i){}\b", pattern)).ok()
+}
+
+/// 解析主题 YAML 后按结构替换敏感字段再重新输出；解析不了的主题无法确认脱敏完整，整个略去
+fn redact_scripts(content: &str) -> String {
+    let Ok(mut theme) = serde_yaml::from_str::<serde_yaml::Value>(content) else {
+        return format!("# {}: theme is not valid YAML\n", REDACTED);
+    };
+    redact_value(&mut theme);
+    serde_yaml::to_string(&theme)
+        .unwrap_or_else(|_| format!("# {}: failed to serialize theme\n", REDACTED))
+}
+
+fn redact_value(value: &mut serde_yaml::Value) {
+    match value {
+        serde_yaml::Value::Mapping(mapping) => {
+            for (key, value) in mapping.iter_mut() {
+                match key.as_str() {
+                    Some(key) if REDACTED_KEYS.contains(&key) => {
+                        *value = serde_yaml::Value::from(REDACTED);
+                    }
+                    Some("stylesheets") => {
+                        if let Some(stylesheets) = value.as_sequence_mut() {
+                            stylesheets.fill(serde_yaml::Value::from(REDACTED));
+                        }
+                    }
+                    _ => redact_value(value),
+                }
+            }
+        }
+        serde_yaml::Value::Sequence(items) => items.iter_mut().for_each(redact_value),
+        serde_yaml::Value::Tagged(tagged) => redact_value(&mut tagged.value),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const THEME: &str = r##"name: Secret
+asarPatches:
+  webcontent/messenger-next.asar:
+    - kind: main-script
+      subject: messenger
+      colorOverrides:
+        --bg: "#fff"
+      customScript: |
+        fetch('https://example.com/?token=secret')
+    - kind: html
+      subject: messenger
+      stylesheets:
+        - https://example.com/private.css?user=secret
+      customCss: >
+        .secret { color: red; }
+    - kind: css
+      path: dist/app.css
+      customCss: ".inline { content: 'secret' }"
+"##;
+
+    #[test]
+    fn redacts_scripts_styles_and_stylesheet_urls() {
+        let redacted = redact_scripts(THEME);
+        assert!(!redacted.contains("secret"), "{}", redacted);
+
+        let theme = serde_yaml::from_str::<serde_yaml::Value>(&redacted).unwrap();
+        let patches = theme["asarPatches"]["webcontent/messenger-next.asar"]
+            .as_sequence()
+            .unwrap();
+        assert_eq!(patches[0]["customScript"], REDACTED);
+        assert_eq!(patches[0]["colorOverrides"]["--bg"], "#fff");
+        assert_eq!(patches[1]["stylesheets"][0], REDACTED);
+        assert_eq!(patches[1]["customCss"], REDACTED);
+        assert_eq!(patches[2]["customCss"], REDACTED);
+        assert_eq!(patches[2]["path"], "dist/app.css");
+        assert_eq!(theme["name"], "Secret");
+    }
+
+    #[test]
+    fn redacts_user_profile_in_any_spelling() {
+        let profile = user_profile_pattern(r"C:\Users\Bob").unwrap();
+        let text =
+            r#"C:\Users\Bob\AppData {"path":"c:\\users\\bob\\Lark"} C:/Users/Bob/x C:\Users\Bobby"#;
+        assert_eq!(
+            profile.replace_all(text, USER_PROFILE),
+            r#"%USERPROFILE%\AppData {"path":"%USERPROFILE%\\Lark"} %USERPROFILE%/x C:\Users\Bobby"#
+        );
+    }
+
+    #[test]
+    fn omits_unparsable_themes() {
+        let redacted = redact_scripts("customScript: [unterminated\n  secret");
+        assert!(!redacted.contains("secret"));
+    }
+}
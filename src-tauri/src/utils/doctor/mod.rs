@@ -0,0 +1,295 @@
+pub mod bundle;
+
+use std::{
+    collections::BTreeSet,
+    fs,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use memchr::memmem;
+
+use crate::{
+    error::WalResult,
+    shared::lark_variant_session,
+    utils::{
+        file::backup::{
+            find_backups_recursively, make_backup_path, make_previous_path, make_temp_path,
+            reverse_make_backup_path,
+        },
+        lark::{
+            file::LarkAsarFile,
+            find::{validate_install_dir, LarkFinder},
+            lark::LarkLocation,
+            patch::PATCH_MARKER_STARTS,
+        },
+        platform::process::is_process_running,
+        theme_store,
+    },
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CheckStatus {
+    Pass,
+    Warn,
+    Fail,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DoctorCheck {
+    /// 检查项标识，如 install、asar:resources/app.asar
+    pub id: String,
+    pub status: CheckStatus,
+    pub detail: String,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DoctorReport {
+    pub variant: String,
+    /// Unix 时间戳（秒）
+    pub generated_at: u64,
+    /// 所有检查项中最差的结果
+    pub status: CheckStatus,
+    pub location: Option<LarkLocation>,
+    pub checks: Vec<DoctorCheck>,
+}
+
+impl DoctorReport {
+    fn push(&mut self, id: impl Into<String>, status: CheckStatus, detail: impl Into<String>) {
+        self.status = self.status.max(status);
+        self.checks.push(DoctorCheck {
+            id: id.into(),
+            status,
+            detail: detail.into(),
+        });
+    }
+}
+
+/// 一次性检查安装、asar、补丁标记、备份、主题库和进程状态，供排查“主题不生效”
+pub fn run_doctor(app_data_dir: &Path, variant: Option<&str>) -> WalResult<DoctorReport> {
+    let (variant, executable, location) = lark_variant_session(variant, |helper| {
+        let location = helper.validate().ok().and_then(|_| helper.get_location());
+        Ok((
+            helper.variant().clone(),
+            helper.variant().executable.clone(),
+            location,
+        ))
+    })?;
+
+    let mut report = DoctorReport {
+        variant: variant.id.clone(),
+        generated_at: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0),
+        status: CheckStatus::Pass,
+        location: location.clone(),
+        checks: Vec::new(),
+    };
+
+    match &location {
+        Some(location) => {
+            report.push(
+                "install",
+                CheckStatus::Pass,
+                format!("{} ({:?})", location.install_dir, location.source),
+            );
+            let validation = validate_install_dir(&variant, &location.install_dir);
+            match validation.version {
+                Some(version) => report.push("version", CheckStatus::Pass, version),
+                None => report.push("version", CheckStatus::Fail, "lark.ini is unreadable"),
+            }
+            if validation.asar_files.is_empty() {
+                report.push("asars", CheckStatus::Fail, "no asar in version directory");
+            } else if !validation.missing_asars.is_empty() {
+                report.push(
+                    "asars",
+                    CheckStatus::Fail,
+                    format!("missing: {}", validation.missing_asars.join(", ")),
+                );
+            }
+            for asar_path in &validation.asar_files {
+                check_asar(&mut report, &location.path, asar_path);
+            }
+            check_backups(&mut report, &location.path);
+        }
+        None => report.push("install", CheckStatus::Fail, "installation not found"),
+    }
+
+    check_themes(&mut report, app_data_dir);
+
+    if is_process_running(&executable) {
+        report.push(
+            "process",
+            CheckStatus::Warn,
+            format!("{} is running; close it before applying", executable),
+        );
+    } else {
+        report.push("process", CheckStatus::Pass, "not running");
+    }
+    Ok(report)
+}
+
+/// 能否解析，是否带补丁标记，补丁与备份是否对得上
+fn check_asar(report: &mut DoctorReport, base_path: &str, asar_path: &str) {
+    let id = format!("asar:{}", asar_path);
+    let file = match LarkAsarFile::new(base_path.to_string(), asar_path.to_string()) {
+        Ok(file) => file,
+        Err(e) => {
+            report.push(id, CheckStatus::Fail, format!("failed to parse: {:?}", e));
+            return;
+        }
+    };
+    let patched = file
+        .files()
+        .filter(|path| {
+            matches!(
+                path.extension(),
+                Some("js" | "mjs" | "cjs" | "css" | "html")
+            )
+        })
+        .filter(|path| {
+            file.data(path).is_some_and(|data| {
+                PATCH_MARKER_STARTS
+                    .iter()
+                    .any(|marker| memmem::find(data, marker.as_bytes()).is_some())
+            })
+        })
+        .map(|path| path.to_string())
+        .collect::<Vec<_>>();
+    let has_backup = Path::new(&make_backup_path(file.asar_absolute_path())).is_file();
+
+    let (status, detail) = match (patched.is_empty(), has_backup) {
+        (true, false) => (CheckStatus::Pass, "unpatched".to_string()),
+        (true, true) => (
+            CheckStatus::Warn,
+            "backup exists but no patch markers found; the client may have been updated"
+                .to_string(),
+        ),
+        (false, true) => (
+            CheckStatus::Pass,
+            format!("patched: {}", patched.join(", ")),
+        ),
+        (false, false) => (
+            CheckStatus::Warn,
+            format!("patched without backup: {}", patched.join(", ")),
+        ),
+    };
+    report.push(id, status, detail);
+}
+
+/// 每个备份都应有对应的原文件，且不应残留应用中途的临时文件
+fn check_backups(report: &mut DoctorReport, base_path: &str) {
+    let backups = match find_backups_recursively(base_path) {
+        Ok(backups) => backups,
+        Err(e) => {
+            report.push("backups", CheckStatus::Fail, format!("{:?}", e));
+            return;
+        }
+    };
+    let orphans = backups
+        .iter()
+        .map(|backup| reverse_make_backup_path(backup))
+        .filter(|original| !Path::new(original).is_file())
+        .collect::<Vec<_>>();
+    let leftovers = backups
+        .iter()
+        .map(|backup| reverse_make_backup_path(backup))
+        .flat_map(|original| [make_temp_path(&original), make_previous_path(&original)])
+        .filter(|path| Path::new(path).exists())
+        .collect::<Vec<_>>();
+
+    if !orphans.is_empty() {
+        report.push(
+            "backups",
+            CheckStatus::Fail,
+            format!("backups without original: {}", orphans.join(", ")),
+        );
+    } else if !leftovers.is_empty() {
+        report.push(
+            "backups",
+            CheckStatus::Warn,
+            format!(
+                "leftovers of an interrupted apply: {}",
+                leftovers.join(", ")
+            ),
+        );
+    } else {
+        report.push(
+            "backups",
+            CheckStatus::Pass,
+            format!("{} backup(s)", backups.len()),
+        );
+    }
+}
+
+/// 清单中的每一项都要有文件；目录中没被清单引用的文件只提示
+fn check_themes(report: &mut DoctorReport, app_data_dir: &Path) {
+    let manifest = theme_store::read_manifest(app_data_dir)
+        .ok()
+        .and_then(|content| serde_json::from_str::<serde_json::Value>(&content).ok());
+    let Some(themes) = manifest
+        .as_ref()
+        .and_then(|manifest| manifest.get("themes"))
+        .and_then(|themes| themes.as_array())
+    else {
+        report.push("themes", CheckStatus::Fail, "themes.json is unreadable");
+        return;
+    };
+
+    let mut ids = BTreeSet::new();
+    let mut duplicates = Vec::new();
+    let mut referenced = BTreeSet::new();
+    let mut missing = Vec::new();
+    let themes_dir = theme_store::themes_dir(app_data_dir);
+    for theme in themes {
+        let id = theme.get("id").and_then(|id| id.as_str()).unwrap_or("");
+        let file_name = theme
+            .get("fileName")
+            .and_then(|name| name.as_str())
+            .unwrap_or("");
+        if !ids.insert(id.to_string()) {
+            duplicates.push(id.to_string());
+        }
+        referenced.insert(file_name.to_string());
+        if file_name.is_empty() || !themes_dir.join(file_name).is_file() {
+            missing.push(id.to_string());
+        }
+    }
+    let unreferenced = fs::read_dir(&themes_dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|entry| entry.file_name().to_string_lossy().to_string())
+                .filter(|name| !referenced.contains(name))
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+
+    if !missing.is_empty() {
+        report.push(
+            "themes",
+            CheckStatus::Fail,
+            format!("theme files missing: {}", missing.join(", ")),
+        );
+    } else if !duplicates.is_empty() || !unreferenced.is_empty() {
+        report.push(
+            "themes",
+            CheckStatus::Warn,
+            format!(
+                "duplicate ids: [{}], files not in themes.json: [{}]",
+                duplicates.join(", "),
+                unreferenced.join(", ")
+            ),
+        );
+    } else {
+        report.push(
+            "themes",
+            CheckStatus::Pass,
+            format!("{} theme(s)", themes.len()),
+        );
+    }
+}
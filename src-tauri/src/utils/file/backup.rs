@@ -7,6 +7,21 @@ pub fn make_backup_path(path: &str) -> String {
     backup_path
 }
 
+/// 备份先写到这里再改名，中途退出不会留下半截的 .wal-backup
+pub fn make_backup_temp_path(path: &str) -> String {
+    format!("{}.tmp", make_backup_path(path))
+}
+
+/// 写入新 asar 时使用的临时文件，写完后再替换目标
+pub fn make_temp_path(path: &str) -> String {
+    format!("{}.wal-tmp", path)
+}
+
+/// 替换目标前原文件改名到这里，替换失败时用来回退
+pub fn make_previous_path(path: &str) -> String {
+    format!("{}.wal-prev", path)
+}
+
 pub fn reverse_make_backup_path(path: &str) -> String {
     if path.contains(".wal-backup") {
         path.replace(".wal-backup", "")
@@ -35,7 +50,15 @@ pub fn create_backup(path: &str) -> WalResult<()> {
     if backup_exists(path)? {
         return Ok(());
     }
-    fs::copy(path, &backup_path).map_err(|_| WalError::IoError)?;
+    let temp_path = make_backup_temp_path(path);
+    let copied = fs::copy(path, &temp_path)
+        .and_then(|_| fs::OpenOptions::new().write(true).open(&temp_path))
+        .and_then(|file| file.sync_all())
+        .and_then(|_| fs::rename(&temp_path, &backup_path));
+    if copied.is_err() {
+        let _ = fs::remove_file(&temp_path);
+        return Err(WalError::IoError);
+    }
     Ok(())
 }
 
@@ -0,0 +1,288 @@
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{LazyLock, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    error::{WalError, WalResult},
+    info,
+    shared::get_app_data_dir,
+    utils::{
+        file::{
+            backup::{make_backup_temp_path, make_previous_path, make_temp_path},
+            lock::lock_install_dir,
+        },
+        platform::process::{is_process_alive, process_start_time},
+    },
+    warn,
+};
+
+/// 写了一半的日志临时文件超过这么久没有改名，视为中断的进程留下的
+const STALE_TEMP_JOURNAL_AGE: Duration = Duration::from_secs(60);
+
+/// 启动时恢复的结果，供前端在订阅事件之前错过时查询
+static RECOVERED_APPLIES: LazyLock<Mutex<Vec<RecoveredApply>>> =
+    LazyLock::new(|| Mutex::new(Vec::new()));
+
+/// 日志目录：<app_data>/journal，每次应用一个文件
+pub fn journal_dir(app_data_dir: &Path) -> PathBuf {
+    app_data_dir.join("journal")
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JournalRecord {
+    pub session_id: String,
+    pub pid: u32,
+    /// 写日志的进程的创建时间，用来排除复用了同一 PID 的进程；旧版日志没有此项
+    #[serde(default)]
+    pub process_started_at: Option<u64>,
+    /// 应用时加锁的目录，重放时同样要先拿到这把锁；旧版日志没有此项
+    #[serde(default)]
+    pub lock_dir: Option<String>,
+    /// Unix 时间戳（秒）
+    pub started_at: u64,
+    /// 所有临时文件都已写完，开始替换目标
+    pub staged: bool,
+    /// 目标 asar 的绝对路径
+    pub targets: Vec<String>,
+}
+
+/// 一次应用的预写日志。每一步开始之前先落盘，进程中途退出时下次启动据此补完或回退。
+/// 未设置应用数据目录时不记录。
+pub struct ApplyJournal {
+    path: Option<PathBuf>,
+    record: JournalRecord,
+}
+
+impl ApplyJournal {
+    pub fn begin(session_id: &str, lock_dir: &Path, targets: &[String]) -> WalResult<Self> {
+        let journal = Self {
+            path: get_app_data_dir()
+                .map(|dir| journal_dir(dir).join(format!("{}.json", session_id))),
+            record: JournalRecord {
+                session_id: session_id.to_string(),
+                pid: std::process::id(),
+                process_started_at: process_start_time(std::process::id()),
+                lock_dir: Some(lock_dir.to_string_lossy().to_string()),
+                started_at: SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|d| d.as_secs())
+                    .unwrap_or(0),
+                staged: false,
+                targets: targets.to_vec(),
+            },
+        };
+        journal.persist()?;
+        Ok(journal)
+    }
+
+    pub fn mark_staged(&mut self) -> WalResult<()> {
+        self.record.staged = true;
+        self.persist()
+    }
+
+    /// 应用结束（成功、失败已回退或取消），删除日志
+    pub fn finish(self) {
+        if let Some(path) = &self.path {
+            let _ = fs::remove_file(path);
+        }
+    }
+
+    fn persist(&self) -> WalResult<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|_| WalError::IoError)?;
+        }
+        let content =
+            serde_json::to_vec_pretty(&self.record).map_err(|_| WalError::SerializationError)?;
+        // 先写临时文件再替换，避免日志本身写了一半
+        let temp_path = path.with_extension("json.tmp");
+        let mut file = fs::File::create(&temp_path).map_err(|_| WalError::IoError)?;
+        file.write_all(&content).map_err(|_| WalError::IoError)?;
+        file.sync_all().map_err(|_| WalError::IoError)?;
+        fs::rename(&temp_path, path).map_err(|_| WalError::IoError)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RecoveryOutcome {
+    /// 中断时已在替换目标，把剩下的替换做完
+    Completed,
+    /// 中断时还在写临时文件，目标未动，清理临时文件
+    RolledBack,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecoveredApply {
+    pub session_id: String,
+    pub started_at: u64,
+    pub outcome: RecoveryOutcome,
+    pub targets: Vec<String>,
+    /// 恢复过程中没能完成的操作
+    pub problems: Vec<String>,
+}
+
+/// 重放上次未结束的应用。仍在运行的其他实例留下的日志不处理；
+/// 目录正被别的会话锁住时也跳过，留到下次启动。重放有未完成的操作时保留日志，下次启动再试。
+pub fn recover_interrupted_applies(app_data_dir: &Path) -> Vec<RecoveredApply> {
+    let Ok(entries) = fs::read_dir(journal_dir(app_data_dir)) else {
+        return Vec::new();
+    };
+    let mut recovered = Vec::new();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.extension().unwrap_or_default() == "tmp" {
+            if is_stale_temp(&path) {
+                let _ = fs::remove_file(&path);
+            }
+            continue;
+        }
+        if path.extension().unwrap_or_default() != "json" {
+            continue;
+        }
+        let record = fs::read(&path)
+            .ok()
+            .and_then(|content| serde_json::from_slice::<JournalRecord>(&content).ok());
+        let Some(record) = record else {
+            warn!("discarding unreadable journal: {}", path.display());
+            let _ = fs::remove_file(&path);
+            continue;
+        };
+        if record.pid != std::process::id()
+            && is_process_alive(record.pid, record.process_started_at)
+        {
+            continue;
+        }
+        // 持有者已退出的锁会被接管；旧版日志没有记录目录，只能不加锁重放
+        let lock = match &record.lock_dir {
+            Some(dir) => match lock_install_dir(dir, &record.session_id) {
+                Ok(lock) => Some(lock),
+                Err(WalError::BusyError(holder)) => {
+                    warn!("skipping journal {}: locked by {}", path.display(), holder);
+                    continue;
+                }
+                Err(e) => {
+                    warn!("failed to lock {} for recovery: {:?}", dir, e);
+                    None
+                }
+            },
+            None => None,
+        };
+        info!(
+            "recovering interrupted apply of session {}",
+            record.session_id
+        );
+        let replayed = replay(&record);
+        if replayed.problems.is_empty() {
+            let _ = fs::remove_file(&path);
+        } else {
+            warn!(
+                "keeping journal {} after failed recovery: {:?}",
+                path.display(),
+                replayed.problems
+            );
+        }
+        recovered.push(replayed);
+        drop(lock);
+    }
+
+    if let Ok(mut applies) = RECOVERED_APPLIES.lock() {
+        applies.extend(recovered.iter().cloned());
+    }
+    recovered
+}
+
+/// 仍在运行的实例可能正要把临时文件改名，只清理有一阵子没动过的
+fn is_stale_temp(path: &Path) -> bool {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+        .is_some_and(|age| age >= STALE_TEMP_JOURNAL_AGE)
+}
+
+pub fn recovered_applies() -> WalResult<Vec<RecoveredApply>> {
+    let applies = RECOVERED_APPLIES.lock().map_err(|_| WalError::LockError)?;
+    Ok(applies.clone())
+}
+
+fn replay(record: &JournalRecord) -> RecoveredApply {
+    let mut problems = Vec::new();
+    let mut check = |result: std::io::Result<()>, action: &str, path: &str| {
+        if result.is_err() {
+            problems.push(format!("{} failed: {}", action, path));
+        }
+    };
+
+    for path in &record.targets {
+        let temp_path = make_temp_path(path);
+        let previous_path = make_previous_path(path);
+        let backup_temp_path = make_backup_temp_path(path);
+        let target_exists = Path::new(path).exists();
+
+        // 备份写到一半时中断，正式的备份还不存在，残留的临时文件没有用处
+        if Path::new(&backup_temp_path).exists() {
+            check(
+                fs::remove_file(&backup_temp_path),
+                "clean up",
+                &backup_temp_path,
+            );
+        }
+
+        if record.staged {
+            if Path::new(&temp_path).exists() {
+                if target_exists {
+                    let moved = fs::rename(path, &previous_path);
+                    if moved.is_err() {
+                        check(moved, "move aside", path);
+                        continue;
+                    }
+                }
+                let swapped = fs::rename(&temp_path, path);
+                if swapped.is_err() {
+                    check(swapped, "swap", path);
+                    // .wal-prev 可能是唯一完好的副本，换不过去就放回原处
+                    if !Path::new(path).exists() && Path::new(&previous_path).exists() {
+                        check(fs::rename(&previous_path, path), "restore", path);
+                    }
+                    continue;
+                }
+            } else if !target_exists && Path::new(&previous_path).exists() {
+                // 原文件已挪开但临时文件不见了，只能放回原文件
+                check(fs::rename(&previous_path, path), "restore", path);
+                continue;
+            }
+            // 只有目标确实是新文件时才删除挪开的原文件
+            if Path::new(&previous_path).exists() {
+                check(fs::remove_file(&previous_path), "clean up", &previous_path);
+            }
+        } else {
+            if Path::new(&temp_path).exists() {
+                check(fs::remove_file(&temp_path), "clean up", &temp_path);
+            }
+            if !target_exists && Path::new(&previous_path).exists() {
+                check(fs::rename(&previous_path, path), "restore", path);
+            }
+        }
+    }
+
+    RecoveredApply {
+        session_id: record.session_id.clone(),
+        started_at: record.started_at,
+        outcome: if record.staged {
+            RecoveryOutcome::Completed
+        } else {
+            RecoveryOutcome::RolledBack
+        },
+        targets: record.targets.clone(),
+        problems,
+    }
+}
@@ -0,0 +1,122 @@
+use std::{
+    fmt, fs,
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use crate::{
+    error::{WalError, WalResult},
+    utils::platform::process::{is_process_alive, process_start_time},
+    warn,
+};
+
+/// 安装目录下的锁文件名
+const LOCK_FILE_NAME: &str = ".wal-lock";
+/// 锁文件刚创建、内容还没写完时不当作残留
+const LOCK_WRITE_GRACE: Duration = Duration::from_secs(5);
+
+/// 锁文件内容：持有锁的进程与会话
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LockHolder {
+    pub pid: u32,
+    /// 持有者进程的创建时间，PID 被复用时据此认出残留的锁；旧版锁文件没有此项
+    #[serde(default)]
+    pub process_started_at: Option<u64>,
+    pub session_id: String,
+}
+
+impl fmt::Display for LockHolder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "session {} (pid {})", self.session_id, self.pid)
+    }
+}
+
+/// 安装目录的咨询锁，离开作用域时删除锁文件
+pub struct InstallDirLock {
+    path: PathBuf,
+}
+
+impl Drop for InstallDirLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+impl InstallDirLock {
+    /// 被锁住的目录
+    pub fn dir(&self) -> &Path {
+        self.path.parent().unwrap_or(Path::new(""))
+    }
+}
+
+/// 为修改安装目录的操作加锁。其他会话或其他进程持有锁时返回 BusyError；
+/// 持有者进程已经退出（或 PID 已被别的进程复用）的锁视为残留，直接接管。
+pub fn lock_install_dir(dir: &str, session_id: &str) -> WalResult<InstallDirLock> {
+    let path = Path::new(dir).join(LOCK_FILE_NAME);
+    let holder = LockHolder {
+        pid: std::process::id(),
+        process_started_at: process_start_time(std::process::id()),
+        session_id: session_id.to_string(),
+    };
+    let content = serde_json::to_vec(&holder).map_err(|_| WalError::SerializationError)?;
+
+    for _ in 0..3 {
+        match fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+        {
+            Ok(mut file) => {
+                file.write_all(&content).map_err(|_| WalError::IoError)?;
+                return Ok(InstallDirLock { path });
+            }
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                // 读之前刚被释放，直接重试
+                let Ok(existing) = fs::read(&path) else {
+                    continue;
+                };
+                match serde_json::from_slice::<LockHolder>(&existing).ok() {
+                    Some(current) if is_process_alive(current.pid, current.process_started_at) => {
+                        return Err(WalError::BusyError(current.to_string()));
+                    }
+                    None if is_fresh(&path) => {
+                        return Err(WalError::BusyError("another process".to_string()));
+                    }
+                    _ => take_over_stale(&path, &existing),
+                }
+            }
+            Err(_) => return Err(WalError::IoError),
+        }
+    }
+    Err(WalError::IoError)
+}
+
+/// 删除残留的锁。先把锁文件改名到只有自己知道的位置再核对内容：改名是原子的，
+/// 几个进程同时接管同一把残留锁时只有一个能改名成功；
+/// 改走的若已不是先前读到的残留锁（别人刚接管并写入了新锁），不覆盖地放回原处
+fn take_over_stale(path: &Path, stale: &[u8]) {
+    let aside = path.with_file_name(format!(
+        "{}.{}",
+        LOCK_FILE_NAME,
+        uuid::Uuid::new_v4().simple()
+    ));
+    if fs::rename(path, &aside).is_err() {
+        return;
+    }
+    if fs::read(&aside).is_ok_and(|content| content == stale) {
+        warn!("removing stale lock: {}", path.display());
+    } else if fs::hard_link(&aside, path).is_err() {
+        warn!("lost a lock taken over concurrently: {}", path.display());
+    }
+    let _ = fs::remove_file(&aside);
+}
+
+fn is_fresh(path: &Path) -> bool {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+        .is_some_and(|age| age < LOCK_WRITE_GRACE)
+}
@@ -1 +1,3 @@
 pub mod backup;
+pub mod journal;
+pub mod lock;
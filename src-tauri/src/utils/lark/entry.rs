@@ -0,0 +1,86 @@
+use std::sync::LazyLock;
+
+use regex::Regex;
+
+use crate::utils::lark::path::AsarPath;
+
+static HTML_COMMENT: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?s)<!--.*?-->").unwrap());
+static SCRIPT_TAG: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?is)<script\b([^>]*)>").unwrap());
+/// 属性名前必须是空白，`data-src`、`xsrc` 之类不算
+static SRC_ATTR: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?i)(?:^|\s)src\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s>]+))"#).unwrap()
+});
+
+/// 公共块的文件名前缀，入口脚本不会以这些开头
+const SHARED_CHUNK_PREFIXES: [&str; 5] =
+    ["vendor", "runtime", "polyfill", "chunk-vendors", "common"];
+
+/// 页面中 `<script src>` 的值，按出现顺序；注释掉的标签不算
+pub fn script_sources(html: &str) -> Vec<String> {
+    let html = HTML_COMMENT.replace_all(html, "");
+    SCRIPT_TAG
+        .captures_iter(&html)
+        .filter_map(|tag| {
+            let attributes = tag.get(1)?.as_str();
+            let src = SRC_ATTR.captures(attributes)?;
+            let value = src.get(1).or(src.get(2)).or(src.get(3))?;
+            Some(value.as_str().to_string())
+        })
+        .collect()
+}
+
+/// 把 src 解析为 asar 内的路径：`/` 开头的相对 asar 根目录，其余相对页面所在目录；
+/// 外部地址返回 None
+pub fn resolve_script_src(page_dir: &AsarPath, src: &str) -> Option<AsarPath> {
+    let src = src.split(['?', '#']).next()?.trim();
+    if src.is_empty() || src.starts_with("//") || src.contains(':') {
+        return None;
+    }
+    // 越过根目录时 new 返回 None
+    match src.strip_prefix('/') {
+        Some(src) => AsarPath::new(src),
+        None => AsarPath::new(&format!("{}/{}", page_dir, src)),
+    }
+}
+
+/// vendor、runtime 等公共块
+pub fn is_shared_chunk(path: &AsarPath) -> bool {
+    let name = path.file_name().to_lowercase();
+    SHARED_CHUNK_PREFIXES
+        .iter()
+        .any(|prefix| name.starts_with(prefix))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn script_sources_ignore_prefixed_src_attributes() {
+        let html = r#"<script data-src="a.js" src="b.js"></script>
+            <script xsrc='c.js'></script>
+            <script type="module" SRC=d.js></script>"#;
+        assert_eq!(script_sources(html), ["b.js", "d.js"]);
+    }
+
+    #[test]
+    fn script_sources_skip_commented_tags() {
+        let html = r#"<!-- <script src="old.js"></script> -->
+            <script src="main.js"></script>"#;
+        assert_eq!(script_sources(html), ["main.js"]);
+    }
+
+    #[test]
+    fn resolve_script_src_from_page_or_root() {
+        let page_dir = AsarPath::parse("dist/pages").unwrap();
+        let resolve = |src| resolve_script_src(&page_dir, src).map(|path| path.to_string());
+        assert_eq!(
+            resolve("main.js?v=1").as_deref(),
+            Some("dist/pages/main.js")
+        );
+        assert_eq!(resolve("/dist/main.js").as_deref(), Some("dist/main.js"));
+        assert_eq!(resolve("https://example.com/a.js"), None);
+        assert_eq!(resolve("//cdn/a.js"), None);
+    }
+}
@@ -0,0 +1,211 @@
+use std::{
+    fs,
+    path::{Component, Path},
+};
+
+use regex::RegexBuilder;
+use serde_json::Value;
+
+use crate::{
+    error::{WalError, WalResult},
+    shared::lark_variant_session,
+    utils::{
+        lark::{file::LarkAsarFile, find::LarkFinder, path::AsarPath},
+        platform::os::utf8_bytes_to_string,
+    },
+};
+
+/// 搜索结果预览在命中位置前后各保留的字节数；打包后的脚本常常整个文件只有一行
+const PREVIEW_CONTEXT: usize = 80;
+/// 未指定上限时最多返回的命中数
+const DEFAULT_MAX_RESULTS: usize = 500;
+/// 开头这么多字节内出现 NUL 的文件视为二进制，不参与搜索
+const BINARY_SNIFF_LEN: usize = 8192;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AsarNodeKind {
+    Directory,
+    File,
+    Link,
+}
+
+/// asar 目录树中的一个节点，路径用 `/` 分隔
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AsarTreeNode {
+    pub name: String,
+    pub path: String,
+    pub kind: AsarNodeKind,
+    /// 文件大小；目录为其下所有文件之和
+    pub size: u64,
+    /// 文件解包在 .unpacked 目录中，不在 asar 内
+    pub unpacked: bool,
+    pub executable: bool,
+    /// 链接指向的路径
+    pub link: Option<String>,
+    pub children: Vec<AsarTreeNode>,
+}
+
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AsarSearchQuery {
+    pub pattern: String,
+    /// 为 false 时按子串匹配
+    #[serde(default)]
+    pub regex: bool,
+    #[serde(default)]
+    pub case_sensitive: bool,
+    /// 只搜索该目录（或文件）之下
+    #[serde(default)]
+    pub path_prefix: Option<String>,
+    #[serde(default)]
+    pub max_results: Option<usize>,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AsarSearchMatch {
+    pub path: String,
+    /// 从 1 开始
+    pub line: usize,
+    /// 行内字节偏移，从 1 开始
+    pub column: usize,
+    pub preview: String,
+}
+
+/// 打开已安装客户端版本目录下的 asar。asar_path 只能是版本目录内的相对路径，
+/// 绝对路径、盘符和 `..` 都会被拒绝，前端或命令行传来的路径不能借此读取目录外的文件
+pub fn open_installed_asar(variant: Option<&str>, asar_path: &str) -> WalResult<LarkAsarFile> {
+    let confined = Path::new(asar_path)
+        .components()
+        .all(|component| matches!(component, Component::Normal(_) | Component::CurDir));
+    if asar_path.is_empty() || !confined {
+        return Err(WalError::PathOutsideInstallError);
+    }
+    let base_path = lark_variant_session(variant, |helper| helper.locate())?;
+    LarkAsarFile::new(base_path, asar_path.to_string())
+}
+
+/// 按头部构建完整目录树
+pub fn asar_tree(file: &LarkAsarFile) -> AsarTreeNode {
+    build_node(file.header().json(), "", "")
+}
+
+fn build_node(node: &Value, name: &str, path: &str) -> AsarTreeNode {
+    let flag = |key: &str| node.get(key).and_then(Value::as_bool) == Some(true);
+    let mut tree = AsarTreeNode {
+        name: name.to_string(),
+        path: path.to_string(),
+        kind: AsarNodeKind::File,
+        size: node.get("size").and_then(Value::as_u64).unwrap_or(0),
+        unpacked: flag("unpacked"),
+        executable: flag("executable"),
+        link: node
+            .get("link")
+            .and_then(Value::as_str)
+            .map(|link| link.to_string()),
+        children: Vec::new(),
+    };
+    if let Some(files) = node.get("files").and_then(Value::as_object) {
+        tree.kind = AsarNodeKind::Directory;
+        tree.children = files
+            .iter()
+            .map(|(child_name, child)| {
+                let child_path = match path {
+                    "" => child_name.clone(),
+                    _ => format!("{}/{}", path, child_name),
+                };
+                build_node(child, child_name, &child_path)
+            })
+            .collect();
+        tree.size = tree.children.iter().map(|child| child.size).sum();
+    } else if tree.link.is_some() {
+        tree.kind = AsarNodeKind::Link;
+    }
+    tree
+}
+
+/// 读取内部文件文本；解包的文件从旁边的 .unpacked 目录读取，都找不到时返回 TargetNotFoundError
+pub fn read_text(file: &LarkAsarFile, inner_path: &str) -> WalResult<String> {
+    if let Ok(path) = file.resolve(inner_path) {
+        return file.read_string(&path);
+    }
+    let path = AsarPath::parse(inner_path)?;
+    let unpacked =
+        Path::new(&format!("{}.unpacked", file.asar_absolute_path())).join(path.as_str());
+    let data = fs::read(unpacked).map_err(|_| WalError::TargetNotFoundError)?;
+    Ok(utf8_bytes_to_string(&data))
+}
+
+/// 在打包的文本文件中搜索；正则无效时返回 InvalidPatternError
+pub fn search(file: &LarkAsarFile, query: &AsarSearchQuery) -> WalResult<Vec<AsarSearchMatch>> {
+    if query.pattern.is_empty() {
+        return Err(WalError::InvalidPatternError);
+    }
+    let pattern = if query.regex {
+        query.pattern.clone()
+    } else {
+        regex::escape(&query.pattern)
+    };
+    let regex = RegexBuilder::new(&pattern)
+        .case_insensitive(!query.case_sensitive)
+        .build()
+        .map_err(|_| WalError::InvalidPatternError)?;
+    let prefix = query.path_prefix.as_deref().and_then(AsarPath::new);
+    let max_results = query.max_results.unwrap_or(DEFAULT_MAX_RESULTS);
+
+    let mut matches = Vec::new();
+    for path in file.files() {
+        if prefix
+            .as_ref()
+            .is_some_and(|prefix| !path.starts_with(prefix))
+        {
+            continue;
+        }
+        let Some(data) = file.data(path) else {
+            continue;
+        };
+        if data[..data.len().min(BINARY_SNIFF_LEN)].contains(&0) {
+            continue;
+        }
+        let text = utf8_bytes_to_string(data);
+        let mut line = 1;
+        let mut line_start = 0;
+        let mut scanned = 0;
+        for found in regex.find_iter(&text) {
+            if matches.len() >= max_results {
+                return Ok(matches);
+            }
+            for (index, _) in text[scanned..found.start()].match_indices('\n') {
+                line += 1;
+                line_start = scanned + index + 1;
+            }
+            scanned = found.start();
+            matches.push(AsarSearchMatch {
+                path: path.to_string(),
+                line,
+                column: found.start() - line_start + 1,
+                preview: preview(&text, found.start(), found.end()),
+            });
+        }
+    }
+    Ok(matches)
+}
+
+/// 命中位置前后一段，不跨行
+fn preview(text: &str, start: usize, end: usize) -> String {
+    let mut from = start.saturating_sub(PREVIEW_CONTEXT);
+    while !text.is_char_boundary(from) {
+        from += 1;
+    }
+    let mut to = (end + PREVIEW_CONTEXT).min(text.len());
+    while !text.is_char_boundary(to) {
+        to -= 1;
+    }
+    let from = text[from..start]
+        .rfind('\n')
+        .map_or(from, |index| from + index + 1);
+    let to = text[end..to].find('\n').map_or(to, |index| end + index);
+    text[from..to].trim().to_string()
+}
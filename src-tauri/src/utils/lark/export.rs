@@ -0,0 +1,102 @@
+use std::{
+    fs,
+    io::{self, Write},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use sha2::{Digest, Sha256};
+
+use crate::{
+    error::{WalError, WalResult},
+    info,
+    utils::lark::{file::LarkAsarFile, patch::PatchUnit, stream::PatchedAsar},
+};
+
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportedPatch {
+    pub inner_path: String,
+    pub size: usize,
+    pub sha256: String,
+}
+
+/// 与导出的 asar 一同写出的说明文件，方便审核与分发
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportManifest {
+    /// 源 asar，相对会话的基准目录
+    pub source_asar: String,
+    pub source_sha256: String,
+    pub output_sha256: String,
+    /// Unix 时间戳（秒）
+    pub exported_at: u64,
+    pub patches: Vec<ExportedPatch>,
+}
+
+/// 说明文件路径：<output>.json
+pub fn sidecar_path(output_path: &str) -> String {
+    format!("{}.json", output_path)
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+fn sha256_file(path: &str) -> WalResult<String> {
+    let mut file = fs::File::open(path).map_err(|_| WalError::IoError)?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher).map_err(|_| WalError::IoError)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+fn is_same_file(a: &str, b: &str) -> bool {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// 把打好补丁的 asar 写到 output_path，不修改源文件；同时写出说明文件
+pub fn export_patched_asar<'a, I>(
+    file: &'a LarkAsarFile,
+    patch_units: I,
+    output_path: &str,
+) -> WalResult<ExportManifest>
+where
+    I: IntoIterator<Item = &'a PatchUnit>,
+    I::IntoIter: Clone,
+{
+    if is_same_file(file.asar_absolute_path(), output_path) {
+        return Err(WalError::InvalidCommandError);
+    }
+    info!("exporting patched asar to {}", output_path);
+
+    let iter = patch_units.into_iter();
+    let patched = PatchedAsar::build(file, iter.clone())?;
+    let mut output =
+        io::BufWriter::new(fs::File::create(output_path).map_err(|_| WalError::IoError)?);
+    patched.write_to(&mut output)?;
+    output.flush().map_err(|_| WalError::IoError)?;
+    drop(output);
+
+    let manifest = ExportManifest {
+        source_asar: file.asar_path().to_string(),
+        source_sha256: sha256_hex(file.binary()),
+        output_sha256: sha256_file(output_path)?,
+        exported_at: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0),
+        patches: iter
+            .map(|unit| ExportedPatch {
+                inner_path: unit.path.to_string(),
+                size: unit.data.len(),
+                sha256: sha256_hex(&unit.data),
+            })
+            .collect(),
+    };
+    let content =
+        serde_json::to_string_pretty(&manifest).map_err(|_| WalError::SerializationError)?;
+    fs::write(sidecar_path(output_path), content).map_err(|_| WalError::IoError)?;
+    Ok(manifest)
+}
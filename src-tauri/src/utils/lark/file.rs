@@ -1,97 +1,197 @@
-use std::{cmp::Ordering, fs, path::PathBuf};
+use std::{collections::BTreeMap, fs, ops::Range, time::SystemTime};
+
+use memmap2::Mmap;
 
 use crate::{
     error::{WalError, WalResult},
-    utils::platform::os::{join_components, utf8_bytes_to_string},
+    utils::{
+        lark::{
+            entry::{is_shared_chunk, resolve_script_src, script_sources},
+            header::AsarHeader,
+            path::AsarPath,
+        },
+        platform::os::{join_components, utf8_bytes_to_string},
+    },
 };
 
-#[derive(Clone, Debug)]
+/// 打开 asar 时的来源：基准目录与文件的修改时间、大小。
+/// 排队的补丁据此确认应用时面对的仍是提交时的那份文件
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AsarStamp {
+    base_path: String,
+    modified: SystemTime,
+    size: u64,
+}
+
+/// 以内存映射方式打开的 asar。头部只在打开时解析一次，
+/// 之后按索引直接从映射中切片读取内部文件，重写时也复用这份头部。
+#[derive(Debug)]
 pub struct LarkAsarFile {
     base_path: String,
     asar_path: String,
-    asar_binary: Vec<u8>,
     asar_absolute_path: String,
-}
-
-impl PartialEq for LarkAsarFile {
-    fn eq(&self, other: &Self) -> bool {
-        self.asar_absolute_path == other.asar_absolute_path
-    }
-}
-
-impl Eq for LarkAsarFile {}
-
-impl PartialOrd for LarkAsarFile {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.asar_absolute_path.cmp(&other.asar_absolute_path))
-    }
-}
-
-impl Ord for LarkAsarFile {
-    fn cmp(&self, other: &Self) -> Ordering {
-        self.asar_absolute_path.cmp(&other.asar_absolute_path)
-    }
+    asar_mmap: Mmap,
+    modified: SystemTime,
+    size: u64,
+    header: AsarHeader,
+    /// 内部文件在映射中的位置
+    entries: BTreeMap<AsarPath, Range<usize>>,
 }
 
 impl LarkAsarFile {
     pub fn new(base_path: String, asar_path: String) -> WalResult<Self> {
         let asar_absolute_path = join_components(&[&base_path, &asar_path])?;
-        let asar_binary = fs::read(&asar_absolute_path).map_err(|_| WalError::IoError)?;
+        let (modified, size) = file_stamp(&asar_absolute_path)?;
+        let file = fs::File::open(&asar_absolute_path).map_err(|_| WalError::IoError)?;
+        // 映射期间文件可能被外部修改，因此每次使用前都要用 is_stale 核对
+        let asar_mmap = unsafe { Mmap::map(&file) }.map_err(|_| WalError::IoError)?;
+
+        let header = AsarHeader::parse(&asar_mmap)?;
+        let mut entries = BTreeMap::new();
+        for entry in header.entries() {
+            let start = header.data_offset() + entry.offset as usize;
+            let end = start + entry.size as usize;
+            if end > asar_mmap.len() {
+                return Err(WalError::AsarStructureError);
+            }
+            entries.insert(entry.path, start..end);
+        }
 
         Ok(Self {
             base_path,
             asar_path,
             asar_absolute_path,
-            asar_binary,
+            asar_mmap,
+            modified,
+            size,
+            header,
+            entries,
         })
     }
 
-    pub fn get_main_script_path(&self, subject: &str) -> WalResult<PathBuf> {
-        let asar_reader = self.create_reader()?;
-        let entry_iter = match asar_reader.read_dir(&PathBuf::from(subject)) {
-            Some(entries) => entries.iter(),
-            None => return Err(WalError::IoError),
-        };
-        match entry_iter
-            .map(|path| {
-                path.clone()
-                    .into_os_string()
-                    .into_string()
-                    .unwrap_or("".to_string())
-            })
-            .filter(|path| path.to_lowercase().ends_with(".js"))
-            .next()
-        {
-            Some(path) => Ok(PathBuf::from(path)),
-            None => Err(WalError::AsarStructureError),
+    /// 磁盘上的文件在打开之后被修改、替换或删除
+    pub fn is_stale(&self) -> bool {
+        match file_stamp(&self.asar_absolute_path) {
+            Ok((modified, size)) => modified != self.modified || size != self.size,
+            Err(_) => true,
         }
     }
 
+    pub fn stamp(&self) -> AsarStamp {
+        AsarStamp {
+            base_path: self.base_path.clone(),
+            modified: self.modified,
+            size: self.size,
+        }
+    }
+
+    /// subject 目录下 index.html 引用、且打包在 asar 内的脚本，按页面中的顺序
+    pub fn get_entry_script_paths(&self, subject: &str) -> WalResult<Vec<AsarPath>> {
+        let index = self
+            .resolve(&format!("{}/index.html", subject))
+            .map_err(|_| WalError::MainScriptNotFoundError)?;
+        let page_dir = index.parent().unwrap_or_default();
+        let html = self
+            .data(&index)
+            .map(utf8_bytes_to_string)
+            .ok_or(WalError::MainScriptNotFoundError)?;
+        let mut scripts = Vec::new();
+        // 页面里的引用由 Electron 按原样查找，这里不放宽大小写
+        for src in script_sources(&html) {
+            let Some(path) = resolve_script_src(&page_dir, &src) else {
+                continue;
+            };
+            if self.entries.contains_key(&path) && !scripts.contains(&path) {
+                scripts.push(path);
+            }
+        }
+        if scripts.is_empty() {
+            return Err(WalError::MainScriptNotFoundError);
+        }
+        Ok(scripts)
+    }
+
+    /// 页面的入口脚本：跳过 vendor、runtime 等公共块后的最后一个脚本
+    pub fn get_main_script_path(&self, subject: &str) -> WalResult<AsarPath> {
+        let scripts = self.get_entry_script_paths(subject)?;
+        scripts
+            .iter()
+            .rev()
+            .find(|path| !is_shared_chunk(path))
+            .or(scripts.last())
+            .cloned()
+            .ok_or(WalError::MainScriptNotFoundError)
+    }
+
     pub fn get_main_script_content(&self, subject: &str) -> WalResult<String> {
         let path = self.get_main_script_path(subject)?;
         self.read_string(&path)
     }
 
-    pub fn read_string(&self, path: &PathBuf) -> WalResult<String> {
+    pub fn read_string(&self, path: &AsarPath) -> WalResult<String> {
         let content = self.read(path)?;
         Ok(utf8_bytes_to_string(&content))
     }
 
-    pub fn read(&self, path: &PathBuf) -> WalResult<Vec<u8>> {
-        let reader = self.create_reader()?;
-        let file = reader.read(path).ok_or(WalError::IoError)?;
-        Ok(file.data().to_vec())
+    pub fn read(&self, path: &AsarPath) -> WalResult<Vec<u8>> {
+        let data = self.data(path).ok_or(WalError::TargetNotFoundError)?;
+        Ok(data.to_vec())
+    }
+
+    /// 内部文件内容，直接引用映射，不拷贝；path 须与头部中的写法完全一致
+    pub fn data(&self, path: &AsarPath) -> Option<&[u8]> {
+        let range = self.entries.get(path)?;
+        Some(&self.asar_mmap[range.clone()])
+    }
+
+    /// 把主题或用户给出的路径解析为头部中的写法。分隔符不限；
+    /// 完全一致的优先，否则接受唯一一个只有大小写不同的文件，有多个或没有时返回 TargetNotFoundError
+    pub fn resolve(&self, path: &str) -> WalResult<AsarPath> {
+        let path = AsarPath::parse(path)?;
+        if self.entries.contains_key(&path) {
+            return Ok(path);
+        }
+        let mut candidates = self.files().filter(|file| file.eq_ignore_case(&path));
+        match (candidates.next(), candidates.next()) {
+            (Some(file), None) => Ok(file.clone()),
+            _ => Err(WalError::TargetNotFoundError),
+        }
+    }
+
+    /// 打包在 asar 内的全部文件
+    pub fn files(&self) -> impl Iterator<Item = &AsarPath> {
+        self.entries.keys()
     }
 
-    pub fn create_reader<'a>(&'a self) -> WalResult<asar::AsarReader<'a>> {
-        create_reader(&self.asar_binary)
+    /// 打开时解析好的头部
+    pub fn header(&self) -> &AsarHeader {
+        &self.header
+    }
+
+    pub fn base_path(&self) -> &str {
+        &self.base_path
     }
 
     pub fn asar_absolute_path(&self) -> &str {
         &self.asar_absolute_path
     }
+
+    /// 相对基准目录的 asar 路径，即提交补丁时传入的路径
+    pub fn asar_path(&self) -> &str {
+        &self.asar_path
+    }
+
+    pub fn binary(&self) -> &[u8] {
+        &self.asar_mmap
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size
+    }
 }
 
-fn create_reader<'a>(data_raw: &'a [u8]) -> WalResult<asar::AsarReader<'a>> {
-    asar::AsarReader::new(&data_raw, None).map_err(|_| WalError::IoError)
+fn file_stamp(path: &str) -> WalResult<(SystemTime, u64)> {
+    let metadata = fs::metadata(path).map_err(|_| WalError::IoError)?;
+    let modified = metadata.modified().map_err(|_| WalError::IoError)?;
+    Ok((modified, metadata.len()))
 }
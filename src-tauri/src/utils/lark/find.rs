@@ -1,38 +1,257 @@
-use std::fs;
+use std::{fs, path::Path};
 
 use crate::{
     error::{WalError, WalResult},
     info,
-    utils::{lark::lark::Lark, platform::os::join_components},
+    utils::{
+        lark::{
+            lark::{Lark, LarkLocation, LarkLocationSource},
+            variant::{expand_env_vars, LarkVariant},
+        },
+        platform::{os::join_components, process::is_process_running},
+    },
+    warn,
 };
 
+/// 在版本目录下查找 asar 时的最大递归深度
+const ASAR_SEARCH_DEPTH: usize = 4;
+
 pub trait LarkFinder {
+    /// 返回缓存的版本目录，未缓存时查找一次
     fn locate(&mut self) -> WalResult<String>;
+    /// 用 lark.ini 和目录是否存在校验缓存，失效时重新查找
+    fn validate(&mut self) -> WalResult<String>;
+    /// 丢弃缓存并重新查找
+    fn refresh(&mut self) -> WalResult<String>;
 }
 
 impl LarkFinder for Lark {
     fn locate(&mut self) -> WalResult<String> {
         match self.get_path() {
             Some(path) => Ok(path),
-            None => match find_lark_path() {
-                Ok(path) => {
-                    self.set_path(&path);
-                    Ok(path)
-                }
-                Err(e) => Err(e),
-            },
+            None => self.refresh(),
         }
     }
+
+    fn validate(&mut self) -> WalResult<String> {
+        let Some(location) = self.get_location() else {
+            return self.refresh();
+        };
+        match read_active_version(self.variant(), &location.install_dir) {
+            Ok(version) if version == location.version && Path::new(&location.path).is_dir() => {
+                Ok(location.path)
+            }
+            _ => {
+                info!("cached lark location is stale: {}", location.path);
+                self.refresh()
+            }
+        }
+    }
+
+    fn refresh(&mut self) -> WalResult<String> {
+        self.invalidate();
+        let location = find_lark_location(self.variant(), self.get_path_override().as_deref())?;
+        self.set_location(&location);
+        Ok(location.path)
+    }
+}
+
+/// 对用户选中的目录做的检查结果，供设置页展示
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LarkInstallValidation {
+    /// lark.ini 所在目录；选中的是版本目录时取其上级
+    pub install_dir: Option<String>,
+    pub version: Option<String>,
+    pub version_dir_exists: bool,
+    /// 版本目录下找到的 asar，相对版本目录
+    pub asar_files: Vec<String>,
+    /// 客户端需要、但版本目录下没有的 asar
+    pub missing_asars: Vec<String>,
+    pub valid: bool,
+}
+
+pub fn validate_install_dir(variant: &LarkVariant, dir: &str) -> LarkInstallValidation {
+    let install_dir = resolve_install_dir(variant, dir).ok();
+    let version = install_dir
+        .as_deref()
+        .and_then(|install_dir| read_active_version(variant, install_dir).ok());
+    let version_dir = match (&install_dir, &version) {
+        (Some(install_dir), Some(version)) => Some(Path::new(install_dir).join(version)),
+        _ => None,
+    };
+    let version_dir_exists = version_dir.as_ref().is_some_and(|dir| dir.is_dir());
+    let asar_files = match &version_dir {
+        Some(version_dir) if version_dir_exists => find_asar_files(version_dir),
+        _ => Vec::new(),
+    };
+    let missing_asars = match &version_dir {
+        Some(version_dir) if version_dir_exists => variant
+            .expected_asars
+            .iter()
+            .filter(|asar| !version_dir.join(asar.as_str()).is_file())
+            .cloned()
+            .collect(),
+        _ => variant.expected_asars.clone(),
+    };
+    let valid = version_dir_exists
+        && missing_asars.is_empty()
+        && (!variant.expected_asars.is_empty() || !asar_files.is_empty());
+    LarkInstallValidation {
+        install_dir,
+        version,
+        version_dir_exists,
+        asar_files,
+        missing_asars,
+        valid,
+    }
+}
+
+/// 一种客户端的检测结果
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DetectedLarkVariant {
+    pub variant: LarkVariant,
+    /// 未安装时为 null
+    pub location: Option<LarkLocation>,
+    pub running: bool,
+}
+
+pub fn detect_variants(helpers: &mut [Lark]) -> Vec<DetectedLarkVariant> {
+    helpers
+        .iter_mut()
+        .map(|helper| DetectedLarkVariant {
+            location: helper.locate().ok().and_then(|_| helper.get_location()),
+            running: is_process_running(&helper.variant().executable),
+            variant: helper.variant().clone(),
+        })
+        .collect()
 }
 
-// HKCU\Software\Feishu\InstallDir
-fn find_lark_path() -> WalResult<String> {
-    let key = windows_registry::CURRENT_USER.open("Software\\Feishu")?;
-    let install_dir = key.get_string("InstallDir")?;
-    let lark_ini_path = join_components(&[&install_dir, "lark.ini"])?;
+fn read_active_version(variant: &LarkVariant, install_dir: &str) -> WalResult<String> {
+    let lark_ini_path = join_components(&[install_dir, &variant.ini_file])?;
     let lark_ini_content = fs::read_to_string(&lark_ini_path).map_err(|_| WalError::IoError)?;
-    let lark_ini_content = lark_ini_content.trim();
+    Ok(lark_ini_content.trim().to_string())
+}
+
+/// 选中的目录本身有 lark.ini 即为安装目录；否则允许选中版本目录，取上级
+fn resolve_install_dir(variant: &LarkVariant, dir: &str) -> WalResult<String> {
+    let dir = Path::new(dir);
+    if dir.join(&variant.ini_file).is_file() {
+        return path_to_string(dir);
+    }
+    match dir.parent() {
+        Some(parent) if parent.join(&variant.ini_file).is_file() => path_to_string(parent),
+        _ => Err(WalError::InvalidInstallDirError),
+    }
+}
 
-    info!("lark active version: {}", lark_ini_content);
-    join_components(&[&install_dir, &lark_ini_content])
+fn path_to_string(path: &Path) -> WalResult<String> {
+    path.to_str()
+        .map(|path| path.to_string())
+        .ok_or(WalError::SystemEncodingError)
+}
+
+fn find_asar_files(version_dir: &Path) -> Vec<String> {
+    let mut asar_files = Vec::new();
+    collect_asar_files(version_dir, version_dir, ASAR_SEARCH_DEPTH, &mut asar_files);
+    asar_files.sort();
+    asar_files
+}
+
+fn collect_asar_files(root: &Path, dir: &Path, depth: usize, out: &mut Vec<String>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_file() && path.extension().unwrap_or_default() == "asar" {
+            let relative = path.strip_prefix(root).unwrap_or(&path);
+            out.push(relative.to_string_lossy().to_string());
+        } else if path.is_dir() && depth > 0 {
+            collect_asar_files(root, &path, depth - 1, out);
+        }
+    }
+}
+
+fn locate_in_dir(
+    variant: &LarkVariant,
+    dir: &str,
+    source: LarkLocationSource,
+) -> WalResult<LarkLocation> {
+    let install_dir = resolve_install_dir(variant, dir)?;
+    let version = read_active_version(variant, &install_dir)?;
+    let path = join_components(&[&install_dir, &version])?;
+    if !Path::new(&path).is_dir() {
+        return Err(WalError::InvalidInstallDirError);
+    }
+    Ok(LarkLocation {
+        install_dir,
+        version,
+        path,
+        source,
+    })
+}
+
+// HKCU / HKLM \<registry_key>\InstallDir
+fn registry_install_dirs(variant: &LarkVariant) -> Vec<String> {
+    let Some(registry_key) = &variant.registry_key else {
+        return Vec::new();
+    };
+    [
+        windows_registry::CURRENT_USER,
+        windows_registry::LOCAL_MACHINE,
+    ]
+    .iter()
+    .filter_map(|root| root.open(registry_key.as_str()).ok())
+    .filter_map(|key| key.get_string("InstallDir").ok())
+    .collect()
+}
+
+fn well_known_install_dirs(variant: &LarkVariant) -> Vec<String> {
+    variant
+        .install_roots
+        .iter()
+        .filter_map(|root| expand_env_vars(root))
+        .collect()
+}
+
+/// 依次尝试：手动指定的目录 → 注册表 → 常见安装目录
+fn find_lark_location(
+    variant: &LarkVariant,
+    path_override: Option<&str>,
+) -> WalResult<LarkLocation> {
+    if let Some(dir) = path_override {
+        match locate_in_dir(variant, dir, LarkLocationSource::Override) {
+            Ok(location) => {
+                info!(
+                    "{} active version: {} (override)",
+                    variant.id, location.version
+                );
+                return Ok(location);
+            }
+            Err(e) => {
+                warn!("lark path override is unusable: {} ({:?})", dir, e);
+            }
+        }
+    }
+
+    let candidates = registry_install_dirs(variant)
+        .into_iter()
+        .map(|dir| (dir, LarkLocationSource::Registry))
+        .chain(
+            well_known_install_dirs(variant)
+                .into_iter()
+                .map(|dir| (dir, LarkLocationSource::WellKnown)),
+        );
+    for (dir, source) in candidates {
+        if let Ok(location) = locate_in_dir(variant, &dir, source) {
+            info!(
+                "{} active version: {} ({:?})",
+                variant.id, location.version, location.source
+            );
+            return Ok(location);
+        }
+    }
+    Err(WalError::LarkNotFoundError)
 }
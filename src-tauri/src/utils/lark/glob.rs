@@ -0,0 +1,125 @@
+use regex::Regex;
+
+use crate::{
+    error::{WalError, WalResult},
+    utils::lark::path::AsarPath,
+};
+
+/// 含通配符时可能是 glob；调用方应先按确切路径查找，找不到再展开
+pub fn is_glob(pattern: &str) -> bool {
+    pattern.contains(['*', '?', '['])
+}
+
+/// asar 内路径的 glob，路径统一用 `/` 分隔：
+/// `*` 匹配除 `/` 外的任意字符，`**` 跨目录，`?` 匹配单个字符，`[...]` 匹配字符集。
+/// 与头部一致，区分大小写
+pub struct Glob {
+    regex: Regex,
+}
+
+impl Glob {
+    pub fn new(pattern: &str) -> WalResult<Self> {
+        let pattern = pattern.replace('\\', "/");
+        let pattern = pattern.trim_start_matches("./").trim_start_matches('/');
+        let mut regex = String::from("^");
+        let mut chars = pattern.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '*' if chars.peek() == Some(&'*') => {
+                    chars.next();
+                    if chars.peek() == Some(&'/') {
+                        chars.next();
+                        regex.push_str("(?:.*/)?");
+                    } else {
+                        regex.push_str(".*");
+                    }
+                }
+                '*' => regex.push_str("[^/]*"),
+                '?' => regex.push_str("[^/]"),
+                // 没有配对的 `]` 时按字面处理
+                '[' if chars.clone().any(|c| c == ']') => {
+                    let mut class = chars.by_ref().take_while(|&c| c != ']').collect::<Vec<_>>();
+                    regex.push('[');
+                    if class.first() == Some(&'!') {
+                        class.remove(0);
+                        regex.push('^');
+                    }
+                    push_class(&mut regex, &class);
+                    regex.push(']');
+                }
+                c => regex.push_str(&regex::escape(&c.to_string())),
+            }
+        }
+        regex.push('$');
+        let regex = Regex::new(&regex).map_err(|_| WalError::InvalidPatternError)?;
+        Ok(Self { regex })
+    }
+
+    pub fn matches(&self, path: &AsarPath) -> bool {
+        self.regex.is_match(path.as_str())
+    }
+}
+
+/// 把字符集的内容译成正则。除了两个字符之间的 `-` 表示范围，其余一律转义：
+/// 正则的字符集里 `&&`、`--`、`~~` 是集合运算，`[` 会开始嵌套字符集
+fn push_class(regex: &mut String, class: &[char]) {
+    let escape = |c: char| regex::escape(&c.to_string());
+    let mut i = 0;
+    while i < class.len() {
+        if class.get(i + 1) == Some(&'-') && i + 2 < class.len() {
+            regex.push_str(&escape(class[i]));
+            regex.push('-');
+            regex.push_str(&escape(class[i + 2]));
+            i += 3;
+        } else {
+            regex.push_str(&escape(class[i]));
+            i += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, path: &str) -> bool {
+        Glob::new(pattern)
+            .unwrap()
+            .matches(&AsarPath::parse(path).unwrap())
+    }
+
+    #[test]
+    fn wildcards_stay_within_directories() {
+        assert!(matches("dist/*.js", "dist/main.js"));
+        assert!(!matches("dist/*.js", "dist/pages/main.js"));
+        assert!(matches("dist/**/*.js", "dist/pages/main.js"));
+        assert!(matches("**/main.js", "main.js"));
+        assert!(matches("dist/m?in.js", "dist/main.js"));
+    }
+
+    #[test]
+    fn class_ranges_and_negation() {
+        assert!(matches("[a-c].js", "b.js"));
+        assert!(!matches("[a-c].js", "d.js"));
+        assert!(matches("[!a-c].js", "d.js"));
+        assert!(!matches("[!a-c].js", "a.js"));
+    }
+
+    #[test]
+    fn class_set_operators_are_literal() {
+        // 不是正则的交集，`&` 与 `b` 都按字面匹配
+        assert!(matches("[a-z&&b].js", "x.js"));
+        assert!(matches("[a-z&&b].js", "&.js"));
+        assert!(matches("f[--].js", "f-.js"));
+        assert!(!matches("f[--].js", "fa.js"));
+        assert!(matches("f[~~].js", "f~.js"));
+        assert!(matches("f[a-].js", "f-.js"));
+    }
+
+    #[test]
+    fn brackets_without_closing_are_literal() {
+        assert!(matches("a[b.js", "a[b.js"));
+        assert!(!matches("a[b.js", "ab.js"));
+        assert!(matches("a[[]b.js", "a[b.js"));
+    }
+}
@@ -0,0 +1,274 @@
+use serde_json::Value;
+
+use crate::{
+    error::{WalError, WalResult},
+    utils::lark::path::AsarPath,
+    warn,
+};
+
+/// asar 开头：size pickle（8 字节）+ header pickle（4 字节长度 + 4 字节字符串长度 + JSON）
+const SIZE_PICKLE_LEN: usize = 8;
+const HEADER_JSON_START: usize = 16;
+
+/// 头部中的一个文件
+#[derive(Clone, Debug)]
+pub struct AsarEntry {
+    pub path: AsarPath,
+    /// 相对数据区起始位置
+    pub offset: u64,
+    pub size: u64,
+}
+
+/// 解析后的 asar 头部
+#[derive(Clone, Debug)]
+pub struct AsarHeader {
+    root: Value,
+    /// 文件数据区在 asar 中的起始位置
+    data_offset: usize,
+}
+
+impl AsarHeader {
+    /// 名字为空、`.`、`..` 或含分隔符的节点会被拼成越出所在目录的路径，
+    /// 整个头部视为损坏，而不是跳过它们让重写时丢掉数据
+    pub fn parse(data: &[u8]) -> WalResult<Self> {
+        let header_size = read_u32(data, 4)?;
+        let json_len = read_u32(data, 12)?;
+        let json = data
+            .get(HEADER_JSON_START..HEADER_JSON_START + json_len)
+            .ok_or(WalError::AsarStructureError)?;
+        let root = serde_json::from_slice(json).map_err(|_| WalError::AsarStructureError)?;
+        check_names(&root)?;
+        Ok(Self {
+            root,
+            data_offset: SIZE_PICKLE_LEN + header_size,
+        })
+    }
+
+    /// 头部 JSON：目录树与各文件的 offset、size、integrity
+    pub fn json(&self) -> &Value {
+        &self.root
+    }
+
+    pub fn data_offset(&self) -> usize {
+        self.data_offset
+    }
+
+    /// 打包在 asar 内的文件，按数据区中的位置排序；解包到 .unpacked 目录的文件和链接不在其中
+    pub fn entries(&self) -> Vec<AsarEntry> {
+        let mut entries = Vec::new();
+        collect_entries(&self.root, &AsarPath::default(), &mut entries);
+        entries.sort_by_key(|entry| entry.offset);
+        entries
+    }
+
+    /// 头部中某个文件的节点
+    pub fn node_mut(&mut self, path: &AsarPath) -> Option<&mut Value> {
+        path.segments().try_fold(&mut self.root, |node, segment| {
+            node.get_mut("files")?.get_mut(segment)
+        })
+    }
+
+    /// 序列化为 asar 开头的两个 pickle
+    pub fn to_bytes(&self) -> WalResult<Vec<u8>> {
+        let json = serde_json::to_vec(&self.root).map_err(|_| WalError::SerializationError)?;
+        let padded_len = json.len().next_multiple_of(4);
+        let payload_size = 4 + padded_len;
+        let header_size = 4 + payload_size;
+
+        let mut bytes = Vec::with_capacity(SIZE_PICKLE_LEN + header_size);
+        for value in [4, header_size, payload_size, json.len()] {
+            bytes.extend_from_slice(&(value as u32).to_le_bytes());
+        }
+        bytes.extend_from_slice(&json);
+        bytes.resize(SIZE_PICKLE_LEN + header_size, 0);
+        Ok(bytes)
+    }
+}
+
+fn read_u32(data: &[u8], at: usize) -> WalResult<usize> {
+    let bytes = data.get(at..at + 4).ok_or(WalError::AsarStructureError)?;
+    Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize)
+}
+
+fn check_names(node: &Value) -> WalResult<()> {
+    let Some(files) = node.get("files").and_then(|files| files.as_object()) else {
+        return Ok(());
+    };
+    for (name, child) in files {
+        if matches!(name.as_str(), "" | "." | "..") || name.contains(['/', '\\']) {
+            warn!({ name = name }; "unsafe entry name in asar header");
+            return Err(WalError::AsarStructureError);
+        }
+        check_names(child)?;
+    }
+    Ok(())
+}
+
+fn collect_entries(node: &Value, path: &AsarPath, out: &mut Vec<AsarEntry>) {
+    let Some(files) = node.get("files").and_then(|files| files.as_object()) else {
+        return;
+    };
+    for (name, child) in files {
+        let child_path = path.join(name);
+        if child.get("files").is_some() {
+            collect_entries(child, &child_path, out);
+            continue;
+        }
+        let unpacked = child.get("unpacked").and_then(Value::as_bool) == Some(true);
+        let offset = child
+            .get("offset")
+            .and_then(Value::as_str)
+            .and_then(|offset| offset.parse().ok());
+        let size = child.get("size").and_then(Value::as_u64);
+        if let (false, Some(offset), Some(size)) = (unpacked, offset, size) {
+            out.push(AsarEntry {
+                path: child_path,
+                offset,
+                size,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use serde_json::json;
+    use sha2::{Digest, Sha256};
+
+    use super::*;
+
+    pub(crate) const PACKAGE_JSON: &[u8] = br#"{"name":"app","main":"dist/main.js"}"#;
+    pub(crate) const MAIN_JS: &[u8] = b"console.log('main');\n";
+    pub(crate) const INDEX_HTML: &[u8] = b"<html><head></head><body></body></html>\n";
+
+    /// 按官方 asar 工具的布局拼出的小包：键按插入顺序排列，
+    /// 带完整性校验的文件、解包到 .unpacked 的文件和链接各一个
+    pub(crate) fn fixture_asar() -> Vec<u8> {
+        let header = json!({
+            "files": {
+                "package.json": { "size": PACKAGE_JSON.len(), "offset": "0" },
+                "dist": {
+                    "files": {
+                        "main.js": {
+                            "size": MAIN_JS.len(),
+                            "integrity": {
+                                "algorithm": "SHA256",
+                                "hash": format!("{:x}", Sha256::digest(MAIN_JS)),
+                                "blockSize": 4 * 1024 * 1024,
+                                "blocks": [format!("{:x}", Sha256::digest(MAIN_JS))],
+                            },
+                            "offset": PACKAGE_JSON.len().to_string(),
+                        },
+                        "native.node": { "size": 4, "unpacked": true },
+                        "current.js": { "link": "dist/main.js" },
+                    },
+                },
+                "index.html": {
+                    "size": INDEX_HTML.len(),
+                    "offset": (PACKAGE_JSON.len() + MAIN_JS.len()).to_string(),
+                },
+            },
+        });
+        pickle(
+            &serde_json::to_string(&header).unwrap(),
+            &[PACKAGE_JSON, MAIN_JS, INDEX_HTML],
+        )
+    }
+
+    /// 不经过 to_bytes，按 pickle 格式手工拼出头部，再接上文件内容
+    pub(crate) fn pickle(json: &str, bodies: &[&[u8]]) -> Vec<u8> {
+        let padded_len = json.len().next_multiple_of(4);
+        let mut bytes = Vec::new();
+        for value in [4, 8 + padded_len, 4 + padded_len, json.len()] {
+            bytes.extend_from_slice(&(value as u32).to_le_bytes());
+        }
+        bytes.extend_from_slice(json.as_bytes());
+        bytes.resize(HEADER_JSON_START + padded_len, 0);
+        for body in bodies {
+            bytes.extend_from_slice(body);
+        }
+        bytes
+    }
+
+    #[test]
+    fn parse_lists_packed_entries_in_data_order() {
+        let asar = fixture_asar();
+        let header = AsarHeader::parse(&asar).unwrap();
+
+        let entries = header
+            .entries()
+            .into_iter()
+            .map(|entry| (entry.path.to_string(), entry.offset, entry.size))
+            .collect::<Vec<_>>();
+        let main_offset = PACKAGE_JSON.len() as u64;
+        let index_offset = main_offset + MAIN_JS.len() as u64;
+        assert_eq!(
+            entries,
+            [
+                ("package.json".to_string(), 0, PACKAGE_JSON.len() as u64),
+                (
+                    "dist/main.js".to_string(),
+                    main_offset,
+                    MAIN_JS.len() as u64
+                ),
+                (
+                    "index.html".to_string(),
+                    index_offset,
+                    INDEX_HTML.len() as u64
+                ),
+            ]
+        );
+        assert_eq!(
+            header.data_offset(),
+            asar.len() - PACKAGE_JSON.len() - MAIN_JS.len() - INDEX_HTML.len()
+        );
+    }
+
+    #[test]
+    fn to_bytes_reproduces_parsed_header() {
+        let asar = fixture_asar();
+        let header = AsarHeader::parse(&asar).unwrap();
+        assert_eq!(header.to_bytes().unwrap(), &asar[..header.data_offset()]);
+    }
+
+    #[test]
+    fn to_bytes_pads_json_to_four_bytes() {
+        for name_len in 1..=4 {
+            let json = serde_json::to_string(&json!({
+                "files": { "a".repeat(name_len): { "size": 0, "offset": "0" } },
+            }))
+            .unwrap();
+            let bytes = AsarHeader::parse(&pickle(&json, &[]))
+                .unwrap()
+                .to_bytes()
+                .unwrap();
+
+            assert_eq!(bytes.len() % 4, 0);
+            assert_eq!(read_u32(&bytes, 4).unwrap(), bytes.len() - SIZE_PICKLE_LEN);
+            assert_eq!(read_u32(&bytes, 12).unwrap(), json.len());
+            let padding = &bytes[HEADER_JSON_START + json.len()..];
+            assert!(padding.len() < 4 && padding.iter().all(|&b| b == 0));
+        }
+    }
+
+    #[test]
+    fn parse_rejects_unsafe_names() {
+        for name in ["..", ".", "", "a/b", "..\\evil.js"] {
+            let json = serde_json::to_string(&json!({
+                "files": { "dist": { "files": { name: { "size": 0, "offset": "0" } } } },
+            }))
+            .unwrap();
+            assert!(
+                AsarHeader::parse(&pickle(&json, &[])).is_err(),
+                "{:?} should be rejected",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn parse_rejects_truncated_header() {
+        let asar = fixture_asar();
+        assert!(AsarHeader::parse(&asar[..HEADER_JSON_START + 4]).is_err());
+    }
+}
@@ -1,17 +1,119 @@
+use std::sync::Arc;
+
+use crate::utils::lark::variant::LarkVariant;
+
+/// 安装位置的来源，按查找优先级排列
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum LarkLocationSource {
+    /// 用户在设置中指定的目录
+    Override,
+    Registry,
+    /// 常见安装目录（便携版、按机器安装等）
+    WellKnown,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LarkLocation {
+    /// 安装目录，即 lark.ini 所在目录
+    pub install_dir: String,
+    /// lark.ini 中记录的当前版本
+    pub version: String,
+    /// 当前版本目录：<install_dir>/<version>
+    pub path: String,
+    pub source: LarkLocationSource,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LarkPathChange {
+    /// 发生变化的客户端，对应 LarkVariant::id
+    pub variant: String,
+    pub previous: Option<LarkLocation>,
+    pub current: LarkLocation,
+}
+
+pub type LarkPathSubscriber = Arc<dyn Fn(&LarkPathChange) + Send + Sync>;
+
 pub struct Lark {
-    path: Option<String>,
+    variant: LarkVariant,
+    location: Option<LarkLocation>,
+    /// 最近一次解析出的位置；缓存失效后仍保留，用来判断路径是否真的变了
+    last_resolved: Option<LarkLocation>,
+    path_override: Option<String>,
+    subscribers: Vec<LarkPathSubscriber>,
 }
 
 impl Lark {
-    pub fn new() -> Self {
-        Self { path: None }
+    pub fn new(variant: LarkVariant) -> Self {
+        Self {
+            variant,
+            location: None,
+            last_resolved: None,
+            path_override: None,
+            subscribers: Vec::new(),
+        }
+    }
+
+    pub fn variant(&self) -> &LarkVariant {
+        &self.variant
+    }
+
+    /// 更新客户端描述；描述有变化时缓存的位置失效，手动指定的目录与订阅者保留
+    pub fn set_variant(&mut self, variant: LarkVariant) {
+        if self.variant != variant {
+            self.variant = variant;
+            self.invalidate();
+        }
     }
 
-    pub fn set_path(&mut self, path: &String) {
-        self.path = Some(path.clone());
+    /// 更换手动指定的目录后缓存随之失效，下次查找时重新解析
+    pub fn set_path_override(&mut self, path_override: Option<String>) {
+        if self.path_override != path_override {
+            self.path_override = path_override;
+            self.invalidate();
+        }
+    }
+
+    pub fn get_path_override(&self) -> Option<String> {
+        self.path_override.clone()
+    }
+
+    /// 更新缓存的位置；版本目录发生变化时通知订阅者
+    pub fn set_location(&mut self, location: &LarkLocation) {
+        self.location = Some(location.clone());
+        let previous = self.last_resolved.replace(location.clone());
+        if previous.as_ref().map(|p| &p.path) == Some(&location.path) {
+            return;
+        }
+        let change = LarkPathChange {
+            variant: self.variant.id.clone(),
+            previous,
+            current: location.clone(),
+        };
+        self.subscribers
+            .iter()
+            .for_each(|subscriber| subscriber(&change));
+    }
+
+    pub fn get_location(&self) -> Option<LarkLocation> {
+        self.location.clone()
     }
 
     pub fn get_path(&self) -> Option<String> {
-        self.path.clone()
+        self.location.as_ref().map(|location| location.path.clone())
+    }
+
+    pub fn invalidate(&mut self) {
+        self.location = None;
+    }
+
+    pub fn subscribe_path_change(&mut self, subscriber: LarkPathSubscriber) {
+        self.subscribers.push(subscriber);
+    }
+
+    pub fn path_subscribers(&self) -> Vec<LarkPathSubscriber> {
+        self.subscribers.clone()
     }
 }
@@ -1,6 +1,13 @@
-pub mod asar_extension;
+pub mod entry;
+pub mod explore;
+pub mod export;
 pub mod file;
 pub mod find;
+pub mod glob;
+pub mod header;
 pub mod lark;
 pub mod patch;
+pub mod path;
+pub mod stream;
+pub mod variant;
 pub mod wrappers;
@@ -1,19 +1,42 @@
-use std::{
-    hash::{Hash, Hasher},
-    path::PathBuf,
+use std::hash::{Hash, Hasher};
+
+use crate::{
+    debug,
+    error::WalResult,
+    utils::lark::{file::LarkAsarFile, path::AsarPath},
 };
 
-use crate::{error::WalResult, info, utils::lark::file::LarkAsarFile};
+/// 注入代码块的起止标记，用来识别和擦除旧补丁
+pub const PATCH_MARKER_START: &str = "\n/** WAL-ASSISTANT-LARK START */";
+pub const PATCH_MARKER_END: &str = "/** WAL-ASSISTANT-LARK END */\n";
+/// 样式表中的标记，代码块追加在文件末尾
+pub const CSS_MARKER_START: &str = "\n/* WAL-ASSISTANT-LARK START */";
+pub const CSS_MARKER_END: &str = "/* WAL-ASSISTANT-LARK END */\n";
+/// 页面中的标记，代码块插在 `</head>` 之前
+pub const HTML_MARKER_START: &str = "\n<!-- WAL-ASSISTANT-LARK START -->";
+pub const HTML_MARKER_END: &str = "<!-- WAL-ASSISTANT-LARK END -->\n";
+/// 各类补丁的起始标记，用于判断文件是否已打过补丁
+pub const PATCH_MARKER_STARTS: [&str; 3] =
+    [PATCH_MARKER_START, CSS_MARKER_START, HTML_MARKER_START];
 
 pub struct PatchPayload {}
 
 pub trait LarkAsarPatch {
-    fn patch_script(&self, path: &PathBuf, script: &str) -> WalResult<PatchUnit>;
+    /// path 须是头部中的写法，不存在时返回 TargetNotFoundError
+    fn patch_script(&self, path: &AsarPath, script: &str) -> WalResult<PatchUnit>;
+    /// 把 css 追加到样式表末尾
+    fn patch_stylesheet(&self, path: &AsarPath, css: &str) -> WalResult<PatchUnit>;
+    /// 把 `<style>`、`<link>` 等标签插入页面的 `</head>` 之前
+    fn patch_html(&self, path: &AsarPath, html: &str) -> WalResult<PatchUnit>;
 }
 
 pub struct PatchUnit {
-    pub path: PathBuf,
+    pub path: AsarPath,
     pub data: Vec<u8>,
+    /// 注入的代码块（不含标记），用于查看排队中的补丁
+    pub script: String,
+    /// 提交者，通常是主题 id
+    pub owner: Option<String>,
 }
 
 impl PartialEq for PatchUnit {
@@ -31,28 +54,21 @@ impl Hash for PatchUnit {
 }
 
 impl LarkAsarPatch for LarkAsarFile {
-    fn patch_script(&self, path: &PathBuf, script: &str) -> WalResult<PatchUnit> {
-        info!("patching script: {}", path.display());
+    fn patch_script(&self, path: &AsarPath, script: &str) -> WalResult<PatchUnit> {
+        debug!({ script = path }; "patching script");
 
         let mut content = self.read_string(path)?;
         let skip_pattern = "use strict";
-        let patch_pattern_start = "\n/** WAL-ASSISTANT-LARK START */";
-        let patch_pattern_end = "/** WAL-ASSISTANT-LARK END */\n";
-
-        if content.contains(patch_pattern_start) && content.contains(patch_pattern_end) {
-            info!("already patched. erasing old patch...");
-            let erase_start_index = content.find(patch_pattern_start).unwrap();
-            let erase_end_index =
-                content.find(patch_pattern_end).unwrap() + patch_pattern_end.len();
-            content.replace_range(erase_start_index..erase_end_index, "");
-        }
+        let patch_pattern_start = PATCH_MARKER_START;
+        let patch_pattern_end = PATCH_MARKER_END;
+        erase_patch(&mut content, patch_pattern_start, patch_pattern_end);
 
         let start_index = if content.contains(skip_pattern) {
-            info!("skipping pattern found. inserting patch after skip pattern...");
+            debug!("skipping pattern found. inserting patch after skip pattern...");
             let skip_pattern_index = content.find(skip_pattern).unwrap();
             skip_pattern_index + skip_pattern.len() + 1
         } else {
-            info!("no skip pattern found. inserting patch at the beginning of the file...");
+            debug!("no skip pattern found. inserting patch at the beginning of the file...");
             0
         };
 
@@ -63,6 +79,70 @@ impl LarkAsarPatch for LarkAsarFile {
         Ok(PatchUnit {
             path: path.clone(),
             data: content.as_bytes().to_vec(),
+            script: script.to_string(),
+            owner: None,
+        })
+    }
+
+    fn patch_stylesheet(&self, path: &AsarPath, css: &str) -> WalResult<PatchUnit> {
+        debug!({ stylesheet = path }; "patching stylesheet");
+
+        let mut content = self.read_string(path)?;
+        erase_patch(&mut content, CSS_MARKER_START, CSS_MARKER_END);
+        if !content.is_empty() && !content.ends_with('\n') {
+            content.push('\n');
+        }
+        content.push_str(&format!(
+            "{}\n{}\n{}",
+            CSS_MARKER_START, css, CSS_MARKER_END
+        ));
+        Ok(PatchUnit {
+            path: path.clone(),
+            data: content.as_bytes().to_vec(),
+            script: css.to_string(),
+            owner: None,
+        })
+    }
+
+    fn patch_html(&self, path: &AsarPath, html: &str) -> WalResult<PatchUnit> {
+        debug!({ page = path }; "patching html");
+
+        let mut content = self.read_string(path)?;
+        erase_patch(&mut content, HTML_MARKER_START, HTML_MARKER_END);
+        // 转小写不改变字节位置，可以直接用来定位
+        let lowercase = content.to_ascii_lowercase();
+        let start_index = match lowercase
+            .find("</head>")
+            .or_else(|| lowercase.find("<body"))
+        {
+            Some(index) => index,
+            None => {
+                debug!("no head or body found. appending patch to the end of the page...");
+                content.len()
+            }
+        };
+        content.insert_str(
+            start_index,
+            &format!("{}\n{}\n{}", HTML_MARKER_START, html, HTML_MARKER_END),
+        );
+        Ok(PatchUnit {
+            path: path.clone(),
+            data: content.as_bytes().to_vec(),
+            script: html.to_string(),
+            owner: None,
         })
     }
 }
+
+/// 擦除之前写入的代码块，使重复打补丁的结果保持一致
+fn erase_patch(content: &mut String, start: &str, end: &str) {
+    let (Some(erase_start_index), Some(end_index)) = (content.find(start), content.find(end))
+    else {
+        return;
+    };
+    if end_index < erase_start_index {
+        return;
+    }
+    debug!("already patched. erasing old patch...");
+    content.replace_range(erase_start_index..end_index + end.len(), "");
+}
@@ -0,0 +1,93 @@
+use std::fmt;
+
+use crate::error::{WalError, WalResult};
+
+/// asar 内部路径，统一为 `/` 分隔、不含空段与 `.`、`..` 的形式，根目录为空串。
+/// 头部中的名字区分大小写，相等与排序都按原样比较；
+/// 大小写不同的写法只在 LarkAsarFile::resolve 查找时宽松匹配。
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Serialize)]
+#[serde(transparent)]
+pub struct AsarPath(String);
+
+impl AsarPath {
+    /// 接受 `/` 或 `\` 分隔的写法，开头的 `/`、`./` 忽略；`..` 越过根目录时返回 None
+    pub fn new(path: &str) -> Option<Self> {
+        let mut segments = Vec::new();
+        for segment in path.split(['/', '\\']) {
+            match segment {
+                "" | "." => {}
+                ".." => {
+                    segments.pop()?;
+                }
+                segment => segments.push(segment),
+            }
+        }
+        Some(Self(segments.join("/")))
+    }
+
+    /// 同 new，无法规范化时返回 TargetNotFoundError
+    pub fn parse(path: &str) -> WalResult<Self> {
+        Self::new(path).ok_or(WalError::TargetNotFoundError)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn is_root(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// 各级名字，根目录没有
+    pub fn segments(&self) -> impl Iterator<Item = &str> {
+        self.0.split('/').filter(|segment| !segment.is_empty())
+    }
+
+    /// 头部中的一个名字，原样拼接
+    pub fn join(&self, name: &str) -> Self {
+        match self.is_root() {
+            true => Self(name.to_string()),
+            false => Self(format!("{}/{}", self.0, name)),
+        }
+    }
+
+    pub fn parent(&self) -> Option<Self> {
+        if self.is_root() {
+            return None;
+        }
+        let parent = self.0.rsplit_once('/').map_or("", |(parent, _)| parent);
+        Some(Self(parent.to_string()))
+    }
+
+    pub fn file_name(&self) -> &str {
+        self.0.rsplit('/').next().unwrap_or_default()
+    }
+
+    pub fn extension(&self) -> Option<&str> {
+        match self.file_name().rsplit_once('.') {
+            Some(("", _)) | None => None,
+            Some((_, extension)) => Some(extension),
+        }
+    }
+
+    /// 按整段比较，`a/bc` 不以 `a/b` 开头
+    pub fn starts_with(&self, prefix: &AsarPath) -> bool {
+        prefix.is_root()
+            || self.0 == prefix.0
+            || self
+                .0
+                .strip_prefix(&prefix.0)
+                .is_some_and(|rest| rest.starts_with('/'))
+    }
+
+    /// 忽略大小写时是否相同
+    pub fn eq_ignore_case(&self, other: &AsarPath) -> bool {
+        self.0.to_lowercase() == other.0.to_lowercase()
+    }
+}
+
+impl fmt::Display for AsarPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
@@ -0,0 +1,273 @@
+use std::io::Write;
+
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    debug,
+    error::{WalError, WalResult},
+    utils::lark::{file::LarkAsarFile, patch::PatchUnit},
+    warn,
+};
+
+/// 完整性校验的分块大小，与官方 asar 工具一致
+const INTEGRITY_BLOCK_SIZE: usize = 4 * 1024 * 1024;
+
+/// 打好补丁的 asar：新头部加上按顺序排列的文件内容。
+/// 未修改的文件直接引用源 asar 的映射，写出时原样拷贝，不在内存中拼出整个 asar。
+pub struct PatchedAsar<'a> {
+    header: Vec<u8>,
+    bodies: Vec<Body<'a>>,
+}
+
+/// 写出的一段文件内容
+enum Body<'a> {
+    /// 源 asar 映射里未修改的内容
+    Source(&'a [u8]),
+    /// 补丁替换后的内容
+    Patched(&'a [u8]),
+}
+
+impl Body<'_> {
+    fn data(&self) -> &[u8] {
+        match self {
+            Body::Source(data) | Body::Patched(data) => data,
+        }
+    }
+}
+
+impl<'a> PatchedAsar<'a> {
+    /// 由原头部加上各补丁文件的尺寸变化生成新头部
+    pub fn build<I>(file: &'a LarkAsarFile, patch_units: I) -> WalResult<Self>
+    where
+        I: IntoIterator<Item = &'a PatchUnit>,
+        I::IntoIter: Clone,
+    {
+        debug!("building patched asar header...");
+        let source = file.binary();
+        let mut header = file.header().clone();
+        let data_offset = header.data_offset();
+        let units = patch_units.into_iter();
+        // 头部里找不到的补丁写出时会被悄悄丢掉，提前报错
+        if let Some(unit) = units.clone().find(|unit| file.data(&unit.path).is_none()) {
+            warn!({ path = unit.path }; "patch target not found in asar");
+            return Err(WalError::TargetNotFoundError);
+        }
+
+        let mut bodies = Vec::new();
+        let mut offset = 0u64;
+        for entry in file.header().entries() {
+            let unit = units.clone().find(|unit| unit.path == entry.path);
+            let body = match unit {
+                Some(unit) => Body::Patched(unit.data.as_slice()),
+                None => {
+                    let start = data_offset + entry.offset as usize;
+                    Body::Source(
+                        source
+                            .get(start..start + entry.size as usize)
+                            .ok_or(WalError::AsarStructureError)?,
+                    )
+                }
+            };
+            let data = body.data();
+            let node = header
+                .node_mut(&entry.path)
+                .ok_or(WalError::AsarStructureError)?;
+            node["offset"] = Value::String(offset.to_string());
+            node["size"] = json!(data.len());
+            if unit.is_some() && node.get("integrity").is_some() {
+                node["integrity"] = integrity(data);
+            }
+            offset += data.len() as u64;
+            bodies.push(body);
+        }
+
+        Ok(Self {
+            header: header.to_bytes()?,
+            bodies,
+        })
+    }
+
+    /// 写出后的总大小
+    pub fn total_size(&self) -> u64 {
+        self.segments().map(|(data, _)| data.len() as u64).sum()
+    }
+
+    /// 按写出顺序列出各段内容，以及该段是否直接拷贝自源 asar 的映射
+    pub fn segments(&self) -> impl Iterator<Item = (&[u8], bool)> {
+        std::iter::once((self.header.as_slice(), false)).chain(
+            self.bodies
+                .iter()
+                .map(|body| (body.data(), matches!(body, Body::Source(_)))),
+        )
+    }
+
+    pub fn write_to<W: Write>(&self, output: &mut W) -> WalResult<()> {
+        for (data, _) in self.segments() {
+            output.write_all(data).map_err(|_| WalError::IoError)?;
+        }
+        Ok(())
+    }
+}
+
+fn integrity(data: &[u8]) -> Value {
+    let blocks = data
+        .chunks(INTEGRITY_BLOCK_SIZE)
+        .map(|block| format!("{:x}", Sha256::digest(block)))
+        .collect::<Vec<_>>();
+    json!({
+        "algorithm": "SHA256",
+        "hash": format!("{:x}", Sha256::digest(data)),
+        "blockSize": INTEGRITY_BLOCK_SIZE,
+        "blocks": blocks,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use crate::utils::lark::{
+        header::{
+            tests::{fixture_asar, INDEX_HTML, MAIN_JS, PACKAGE_JSON},
+            AsarHeader,
+        },
+        path::AsarPath,
+    };
+
+    use super::*;
+
+    /// 把 asar 写进独立的临时目录并打开
+    fn open(dir: &PathBuf, name: &str, asar: &[u8]) -> LarkAsarFile {
+        fs::create_dir_all(dir).unwrap();
+        fs::write(dir.join(name), asar).unwrap();
+        LarkAsarFile::new(dir.to_string_lossy().to_string(), name.to_string()).unwrap()
+    }
+
+    fn temp_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("wal-stream-{}", uuid::Uuid::new_v4()))
+    }
+
+    fn write(patched: &PatchedAsar) -> Vec<u8> {
+        let mut output = Vec::new();
+        patched.write_to(&mut output).unwrap();
+        assert_eq!(output.len() as u64, patched.total_size());
+        output
+    }
+
+    #[test]
+    fn build_without_patches_is_byte_identical() {
+        let dir = temp_dir();
+        let asar = fixture_asar();
+        let file = open(&dir, "app.asar", &asar);
+
+        let output = write(&PatchedAsar::build(&file, &Vec::new()).unwrap());
+        assert_eq!(output, asar);
+
+        drop(file);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn build_with_patches_reparses() {
+        let dir = temp_dir();
+        let file = open(&dir, "app.asar", &fixture_asar());
+        let patched_js = b"console.log('main');\n/* patched */\n".to_vec();
+        let units = vec![PatchUnit {
+            path: AsarPath::parse("dist/main.js").unwrap(),
+            data: patched_js.clone(),
+            script: String::new(),
+            owner: None,
+        }];
+
+        let patched = PatchedAsar::build(&file, &units).unwrap();
+        // 只有未修改的文件算作从源 asar 拷贝
+        let source_bytes = patched
+            .segments()
+            .filter(|(_, from_source)| *from_source)
+            .map(|(data, _)| data.len())
+            .sum::<usize>();
+        assert_eq!(source_bytes, PACKAGE_JSON.len() + INDEX_HTML.len());
+        let output = write(&patched);
+        let reopened = open(&dir, "patched.asar", &output);
+        let header = reopened.header();
+
+        // 后面的文件整体后移，内容不变
+        let offsets = header
+            .entries()
+            .into_iter()
+            .map(|entry| (entry.path.to_string(), entry.offset, entry.size))
+            .collect::<Vec<_>>();
+        let main_offset = PACKAGE_JSON.len() as u64;
+        let index_offset = main_offset + patched_js.len() as u64;
+        assert_eq!(
+            offsets,
+            [
+                ("package.json".to_string(), 0, PACKAGE_JSON.len() as u64),
+                (
+                    "dist/main.js".to_string(),
+                    main_offset,
+                    patched_js.len() as u64
+                ),
+                (
+                    "index.html".to_string(),
+                    index_offset,
+                    INDEX_HTML.len() as u64
+                ),
+            ]
+        );
+        let data = |path: &str| reopened.data(&AsarPath::parse(path).unwrap()).unwrap();
+        assert_eq!(data("package.json"), PACKAGE_JSON);
+        assert_eq!(data("dist/main.js"), patched_js.as_slice());
+        assert_eq!(data("index.html"), INDEX_HTML);
+
+        // 完整性校验按新内容重算，整体与分块一致
+        let dist = &header.json()["files"]["dist"]["files"];
+        let hash = format!("{:x}", Sha256::digest(&patched_js));
+        assert_ne!(hash, format!("{:x}", Sha256::digest(MAIN_JS)));
+        assert_eq!(dist["main.js"]["integrity"]["hash"], hash);
+        assert_eq!(dist["main.js"]["integrity"]["blocks"], json!([hash]));
+        assert_eq!(
+            dist["main.js"]["integrity"]["blockSize"],
+            INTEGRITY_BLOCK_SIZE
+        );
+
+        // 解包的文件与链接原样保留
+        assert_eq!(dist["native.node"], json!({ "size": 4, "unpacked": true }));
+        assert_eq!(dist["current.js"], json!({ "link": "dist/main.js" }));
+
+        // 头部按 4 字节对齐，补齐的部分为 0
+        let data_offset = header.data_offset();
+        assert_eq!(data_offset % 4, 0);
+        let json_len = u32::from_le_bytes(output[12..16].try_into().unwrap()) as usize;
+        assert!(output[16 + json_len..data_offset].iter().all(|&b| b == 0));
+        assert_eq!(
+            AsarHeader::parse(&output).unwrap().to_bytes().unwrap(),
+            &output[..data_offset]
+        );
+
+        drop(reopened);
+        drop(file);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn build_rejects_unknown_target() {
+        let dir = temp_dir();
+        let file = open(&dir, "app.asar", &fixture_asar());
+        let units = vec![PatchUnit {
+            path: AsarPath::parse("dist/missing.js").unwrap(),
+            data: Vec::new(),
+            script: String::new(),
+            owner: None,
+        }];
+
+        assert!(matches!(
+            PatchedAsar::build(&file, &units),
+            Err(WalError::TargetNotFoundError)
+        ));
+
+        drop(file);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,64 @@
+pub const DEFAULT_VARIANT_ID: &str = "feishu";
+
+/// 一种飞书客户端的描述：国内版、国际版以及各种私有化部署
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LarkVariant {
+    /// 唯一标识，命令里用它指定目标客户端
+    pub id: String,
+    pub display_name: String,
+    /// 进程名，同时也是版本目录下可执行文件的文件名
+    pub executable: String,
+    /// HKCU / HKLM 下记录 InstallDir 的注册表项；私有化部署可能没有
+    pub registry_key: Option<String>,
+    /// 注册表找不到时依次尝试的安装目录，支持 %VAR% 形式的环境变量
+    pub install_roots: Vec<String>,
+    /// 记录当前版本号的文件名
+    pub ini_file: String,
+    /// 版本目录下必须存在的 asar，相对版本目录；为空时只要求至少有一个 asar
+    #[serde(default)]
+    pub expected_asars: Vec<String>,
+}
+
+impl LarkVariant {
+    fn builtin(id: &str, display_name: &str, name: &str) -> Self {
+        Self {
+            id: id.to_string(),
+            display_name: display_name.to_string(),
+            executable: format!("{}.exe", name),
+            registry_key: Some(format!("Software\\{}", name)),
+            install_roots: ["%LOCALAPPDATA%", "%ProgramFiles%", "%ProgramFiles(x86)%"]
+                .iter()
+                .map(|root| format!("{}\\{}", root, name))
+                .collect(),
+            ini_file: "lark.ini".to_string(),
+            expected_asars: vec!["webcontent/messenger-next.asar".to_string()],
+        }
+    }
+}
+
+pub fn builtin_variants() -> Vec<LarkVariant> {
+    vec![
+        LarkVariant::builtin(DEFAULT_VARIANT_ID, "飞书", "Feishu"),
+        LarkVariant::builtin("lark", "Lark", "Lark"),
+    ]
+}
+
+/// 展开 %VAR% 形式的环境变量；变量不存在或百分号不成对时返回 None
+pub fn expand_env_vars(path: &str) -> Option<String> {
+    let parts = path.split('%').collect::<Vec<_>>();
+    if parts.len() % 2 == 0 {
+        return None;
+    }
+    parts
+        .iter()
+        .enumerate()
+        .map(|(index, part)| {
+            if index % 2 == 1 {
+                std::env::var(part).ok()
+            } else {
+                Some(part.to_string())
+            }
+        })
+        .collect()
+}
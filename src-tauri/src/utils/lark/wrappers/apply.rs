@@ -0,0 +1,153 @@
+use std::{
+    collections::HashMap,
+    io::{self, Write},
+    sync::{LazyLock, Mutex},
+};
+
+use tokio_util::sync::CancellationToken;
+
+use crate::error::{WalError, WalResult};
+
+/// 写入时每累计这么多字节上报一次进度
+const PROGRESS_REPORT_INTERVAL: u64 = 4 * 1024 * 1024;
+
+/// 从源 asar 拷贝内容时的分块大小，让读取进度随写入一起推进
+pub const COPY_CHUNK_SIZE: usize = 1024 * 1024;
+
+static APPLY_TOKENS: LazyLock<Mutex<HashMap<String, CancellationToken>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ApplyPhase {
+    Backup,
+    Rewrite,
+    Write,
+    Done,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApplyProgress {
+    pub asar_path: String,
+    pub asar_index: usize,
+    pub total_asars: usize,
+    pub phase: ApplyPhase,
+    /// 从源 asar 拷贝的字节数，补丁替换的内容不计入
+    pub bytes_read: u64,
+    pub bytes_written: u64,
+}
+
+#[derive(Clone, Debug, Default, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApplySummary {
+    /// 已写入的 asar，相对会话的基准目录
+    pub applied: Vec<String>,
+    pub cancelled: bool,
+    pub bytes_read: u64,
+    pub bytes_written: u64,
+    pub elapsed_ms: u64,
+}
+
+/// 通过 Channel 推给前端的事件
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase", tag = "event", content = "data")]
+pub enum ApplyEvent {
+    #[serde(rename_all = "camelCase")]
+    Started {
+        total_asars: usize,
+    },
+    Progress(ApplyProgress),
+    Finished(ApplySummary),
+    Failed {
+        message: String,
+    },
+}
+
+/// 登记一次应用并返回其取消令牌；同一会话不能同时应用两次
+pub fn begin_apply(session_id: &str) -> WalResult<CancellationToken> {
+    let mut tokens = APPLY_TOKENS.lock().map_err(|_| WalError::LockError)?;
+    if tokens.contains_key(session_id) {
+        return Err(WalError::ApplyInProgressError);
+    }
+    let token = CancellationToken::new();
+    tokens.insert(session_id.to_string(), token.clone());
+    Ok(token)
+}
+
+pub fn finish_apply(session_id: &str) {
+    if let Ok(mut tokens) = APPLY_TOKENS.lock() {
+        tokens.remove(session_id);
+    }
+}
+
+/// 请求取消正在进行的应用；没有进行中的应用时返回 false
+pub fn cancel_apply(session_id: &str) -> WalResult<bool> {
+    let tokens = APPLY_TOKENS.lock().map_err(|_| WalError::LockError)?;
+    match tokens.get(session_id) {
+        Some(token) => {
+            token.cancel();
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+/// 统计读写字节数并在取消时中断写入的 Writer。上报时传入（读取，写入）的字节数
+pub struct ProgressWriter<'a, W: Write> {
+    inner: W,
+    read: u64,
+    written: u64,
+    last_reported: u64,
+    token: &'a CancellationToken,
+    on_progress: &'a mut dyn FnMut(u64, u64),
+}
+
+impl<'a, W: Write> ProgressWriter<'a, W> {
+    pub fn new(
+        inner: W,
+        token: &'a CancellationToken,
+        on_progress: &'a mut dyn FnMut(u64, u64),
+    ) -> Self {
+        Self {
+            inner,
+            read: 0,
+            written: 0,
+            last_reported: 0,
+            token,
+            on_progress,
+        }
+    }
+
+    /// 记下从源 asar 读出、即将写入的字节数，随下一次上报一起发出
+    pub fn record_read(&mut self, count: u64) {
+        self.read += count;
+    }
+
+    pub fn read(&self) -> u64 {
+        self.read
+    }
+
+    pub fn written(&self) -> u64 {
+        self.written
+    }
+}
+
+impl<W: Write> Write for ProgressWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.token.is_cancelled() {
+            return Err(io::Error::other("apply cancelled"));
+        }
+        let count = self.inner.write(buf)?;
+        self.written += count as u64;
+        if self.written - self.last_reported >= PROGRESS_REPORT_INTERVAL {
+            self.last_reported = self.written;
+            (self.on_progress)(self.read, self.written);
+        }
+        Ok(count)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
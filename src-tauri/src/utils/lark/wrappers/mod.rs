@@ -1 +1,3 @@
+pub mod apply;
+pub mod preflight;
 pub mod session;
@@ -0,0 +1,56 @@
+use std::{fs, io::ErrorKind, os::windows::fs::OpenOptionsExt};
+
+/// 写入前检查出的阻塞问题
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase", tag = "kind")]
+pub enum PreflightProblem {
+    /// 客户端正在运行，asar 被占用
+    #[serde(rename_all = "camelCase")]
+    LarkRunning { variant: String },
+    #[serde(rename_all = "camelCase")]
+    AsarMissing { asar_path: String },
+    /// 文件只读，或所在目录无法创建文件（例如未以管理员身份安装到 Program Files）
+    #[serde(rename_all = "camelCase")]
+    NotWritable { asar_path: String },
+    /// 文件被其他进程打开
+    #[serde(rename_all = "camelCase")]
+    FileInUse { asar_path: String },
+    /// 剩余空间不足以放下备份与新 asar
+    #[serde(rename_all = "camelCase")]
+    InsufficientSpace { required: u64, available: u64 },
+}
+
+/// 文件不是只读，且同目录下可以创建临时文件
+pub fn is_writable(path: &str) -> bool {
+    let readonly = fs::metadata(path)
+        .map(|metadata| metadata.permissions().readonly())
+        .unwrap_or(true);
+    if readonly {
+        return false;
+    }
+    // 每次用不同的文件名，同时检查或上次残留的探针文件不会被误判为不可写
+    let probe = format!("{}.wal-probe-{}", path, uuid::Uuid::new_v4().simple());
+    match fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&probe)
+    {
+        Ok(file) => {
+            drop(file);
+            let _ = fs::remove_file(&probe);
+            true
+        }
+        // 文件已存在说明目录里能创建文件
+        Err(e) => e.kind() == ErrorKind::AlreadyExists,
+    }
+}
+
+/// 以独占方式打开文件；其他进程持有句柄或映射时失败
+pub fn is_in_use(path: &str) -> bool {
+    fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .share_mode(0)
+        .open(path)
+        .is_err()
+}
@@ -1,37 +1,117 @@
 use std::{
     collections::{BTreeMap, HashSet},
     fs,
-    path::PathBuf,
-    sync::{Arc, LazyLock, Mutex},
+    io::{self, Write},
+    panic::{self, AssertUnwindSafe},
+    path::Path,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc, Arc, LazyLock, Mutex,
+    },
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
-use asar::AsarWriter;
+use tokio_util::sync::CancellationToken;
 
 use crate::{
+    error,
     error::{WalError, WalResult},
     info,
-    shared::lark_helper_session,
+    shared::lark_variant_session,
     utils::{
-        file::{self, backup::create_backup},
+        file::{
+            self,
+            backup::{backup_exists, create_backup, make_previous_path, make_temp_path},
+            journal::ApplyJournal,
+            lock::{lock_install_dir, InstallDirLock},
+        },
         lark::{
-            asar_extension::AsarPatch,
-            file::LarkAsarFile,
+            export::{self, ExportManifest},
+            file::{AsarStamp, LarkAsarFile},
             find::LarkFinder,
+            glob::{is_glob, Glob},
             patch::{LarkAsarPatch, PatchUnit},
+            path::AsarPath,
+            stream::PatchedAsar,
+            variant::DEFAULT_VARIANT_ID,
+            wrappers::{
+                apply::{
+                    ApplyEvent, ApplyPhase, ApplyProgress, ApplySummary, ProgressWriter,
+                    COPY_CHUNK_SIZE,
+                },
+                preflight::{is_in_use, is_writable, PreflightProblem},
+            },
+        },
+        platform::{
+            os::{free_disk_space, join_components, normalize_path},
+            process::is_process_running,
         },
     },
+    warn,
 };
 
-static SESSIONS: LazyLock<Mutex<Vec<Arc<Mutex<LarkSession>>>>> =
-    LazyLock::new(|| Mutex::new(Vec::new()));
+/// 空闲超过该时长的会话会被回收
+const SESSION_IDLE_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+/// 同时存在的会话上限，超出时回收最久未使用的
+const MAX_SESSIONS: usize = 8;
+/// 所有会话持有的 asar 与补丁数据总量上限
+const MAX_SESSIONS_MEMORY: usize = 1024 * 1024 * 1024;
+/// 并行应用时的最大工作线程数；杀毒软件会扫描每次写入，线程再多也只是排队
+const MAX_APPLY_WORKERS: usize = 4;
+/// glob 展开文件补丁时只接受这些扩展名
+const SCRIPT_EXTENSIONS: [&str; 3] = ["js", "mjs", "cjs"];
+/// glob 展开样式表补丁时只接受这些扩展名
+const STYLESHEET_EXTENSIONS: [&str; 1] = ["css"];
+
+struct SessionEntry {
+    id: String,
+    target: SessionTarget,
+    created_at: SystemTime,
+    session: Arc<Mutex<LarkSession>>,
+}
 
-pub fn create_lark_session() -> WalResult<Arc<Mutex<LarkSession>>> {
-    let session = LarkSession::new();
-    let session = Arc::new(Mutex::new(session));
+static SESSIONS: LazyLock<Mutex<Vec<SessionEntry>>> = LazyLock::new(|| Mutex::new(Vec::new()));
+
+pub fn create_lark_session(variant: Option<&str>) -> WalResult<Arc<Mutex<LarkSession>>> {
+    let variant = variant.unwrap_or(DEFAULT_VARIANT_ID);
+    // 飞书更新或换目录安装后缓存的路径会失效，每次建会话时都重新核对一次
+    match lark_variant_session(Some(variant), |helper| helper.validate()) {
+        Err(WalError::UnknownVariantError) => return Err(WalError::UnknownVariantError),
+        Err(e) => {
+            warn!("failed to validate {} location: {:?}", variant, e);
+        }
+        Ok(_) => {}
+    }
+    register_session(LarkSession::new(SessionTarget::Installed(
+        variant.to_string(),
+    )))
+}
+
+/// 绑定到指定目录的会话，不经过安装检测；目录结构与版本目录相同
+pub fn create_lark_directory_session(base_path: &str) -> WalResult<Arc<Mutex<LarkSession>>> {
+    if !Path::new(base_path).is_dir() {
+        return Err(WalError::InvalidInstallDirError);
+    }
+    register_session(LarkSession::new(SessionTarget::Directory(
+        base_path.to_string(),
+    )))
+}
+
+fn register_session(session: LarkSession) -> WalResult<Arc<Mutex<LarkSession>>> {
+    let id = session.id().to_string();
+    let entry = SessionEntry {
+        id: id.clone(),
+        target: session.target().clone(),
+        created_at: SystemTime::now(),
+        session: Arc::new(Mutex::new(session)),
+    };
+    let session = entry.session.clone();
     SESSIONS
         .lock()
         .map_err(|_| WalError::LockError)?
-        .push(session.clone());
+        .push(entry);
+    collect(Some(&id))?;
     Ok(session)
 }
 
@@ -39,56 +119,210 @@ pub fn get_lark_session(id: &str) -> Option<Arc<Mutex<LarkSession>>> {
     let sessions = SESSIONS.lock().ok()?;
     sessions
         .iter()
-        .find(|session| match session.lock() {
-            Ok(session) => session.id() == id,
-            Err(_) => false,
-        })
-        .cloned()
+        .find(|entry| entry.id == id)
+        .map(|entry| entry.session.clone())
 }
 
 pub fn close_lark_session(id: &str) -> WalResult<()> {
     let mut sessions = SESSIONS.lock().map_err(|_| WalError::LockError)?;
     let index = sessions
         .iter()
-        .position(|s| match s.lock() {
-            Ok(session) => session.id() == id,
-            Err(_) => false,
-        })
+        .position(|entry| entry.id == id)
         .ok_or(WalError::SessionNotFoundError)?;
     sessions.remove(index);
     Ok(())
 }
 
+/// 回收空闲超时的会话；数量或内存超出上限时再按最久未使用的顺序回收。
+/// 正在执行命令（锁被占用）的会话不会被回收。
+pub fn collect_garbage() -> WalResult<()> {
+    collect(None)
+}
+
+fn collect(keep: Option<&str>) -> WalResult<()> {
+    let mut sessions = SESSIONS.lock().map_err(|_| WalError::LockError)?;
+    let now = Instant::now();
+
+    let mut count = sessions.len();
+    let mut memory = 0;
+    let mut candidates = Vec::new();
+    for (index, entry) in sessions.iter().enumerate() {
+        let Ok(session) = entry.session.try_lock() else {
+            continue;
+        };
+        let session_memory = session.memory_usage();
+        memory += session_memory;
+        if Some(entry.id.as_str()) != keep {
+            candidates.push((index, session.last_used(), session_memory));
+        }
+    }
+    candidates.sort_by_key(|(_, last_used, _)| *last_used);
+
+    let mut expired = Vec::new();
+    for (index, last_used, session_memory) in candidates {
+        let idle = now.duration_since(last_used) >= SESSION_IDLE_TIMEOUT;
+        if !idle && count <= MAX_SESSIONS && memory <= MAX_SESSIONS_MEMORY {
+            break;
+        }
+        expired.push(index);
+        count -= 1;
+        memory -= session_memory;
+    }
+
+    expired.sort_unstable_by(|a, b| b.cmp(a));
+    for index in expired {
+        let entry = sessions.remove(index);
+        info!("session {} expired", entry.id);
+    }
+    Ok(())
+}
+
+/// 释放所有会话中位于 base_path 下的 asar 映射。Windows 上任何一个映射都会让替换失败，
+/// 因此修改文件前不能只释放本会话的。调用方自己的会话已被锁住，由它自行释放；
+/// 其他正在执行命令的会话无法释放，由 preflight 的占用检查报告
+fn release_shared_asars(base_path: &str) -> WalResult<()> {
+    let base_key = normalize_path(base_path);
+    let sessions = SESSIONS.lock().map_err(|_| WalError::LockError)?;
+    for entry in sessions.iter() {
+        let Ok(mut session) = entry.session.try_lock() else {
+            continue;
+        };
+        session
+            .asar_cache
+            .retain(|key, _| !Path::new(key).starts_with(&base_key));
+    }
+    Ok(())
+}
+
+/// 会话中某个 asar 的排队情况
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionAsarInfo {
+    pub asar_path: String,
+    pub patch_count: usize,
+    /// 已加载的 asar 大小
+    pub asar_bytes: usize,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionInfo {
+    pub id: String,
+    pub target: SessionTarget,
+    /// Unix 时间戳（秒）
+    pub created_at: u64,
+    pub idle_seconds: u64,
+    /// 会话正在执行命令，此时不统计其内容
+    pub busy: bool,
+    pub memory_bytes: usize,
+    pub asars: Vec<SessionAsarInfo>,
+}
+
+pub fn list_lark_sessions() -> WalResult<Vec<SessionInfo>> {
+    collect_garbage()?;
+    let sessions = SESSIONS.lock().map_err(|_| WalError::LockError)?;
+    Ok(sessions
+        .iter()
+        .map(|entry| {
+            let mut info = SessionInfo {
+                id: entry.id.clone(),
+                target: entry.target.clone(),
+                created_at: entry
+                    .created_at
+                    .duration_since(UNIX_EPOCH)
+                    .map(|d| d.as_secs())
+                    .unwrap_or(0),
+                idle_seconds: 0,
+                busy: true,
+                memory_bytes: 0,
+                asars: Vec::new(),
+            };
+            if let Ok(session) = entry.session.try_lock() {
+                info.busy = false;
+                info.idle_seconds = session.last_used().elapsed().as_secs();
+                info.memory_bytes = session.memory_usage();
+                info.asars = session.asar_infos();
+            }
+            info
+        })
+        .collect())
+}
+
+/// 缺少参数时返回 InvalidCommandError，而不是越界 panic
+fn arg(args: &[String], index: usize) -> WalResult<&str> {
+    args.get(index)
+        .map(|arg| arg.as_str())
+        .ok_or(WalError::InvalidCommandError)
+}
+
 pub fn interpret_command(session_id: &str, command: &str, args: Vec<String>) -> WalResult<String> {
     let session = get_lark_session(session_id).ok_or(WalError::SessionNotFoundError)?;
     let mut session = session.lock().map_err(|_| WalError::LockError)?;
+    session.touch();
     match command {
-        "submit_patch" => {
-            session.submit_patch(args[0].as_str(), args[1].as_str(), args[2].as_str())?;
-            Ok("patch submitted".to_string())
+        "submit_patch" | "dry_run_patch" => {
+            let plan = session.submit_patch(
+                arg(&args, 0)?,
+                arg(&args, 1)?,
+                arg(&args, 2)?,
+                args.get(3)
+                    .map(|owner| owner.as_str())
+                    .filter(|owner| !owner.is_empty()),
+                command == "dry_run_patch",
+            )?;
+            serde_json::to_string(&plan).map_err(|_| WalError::SerializationError)
         }
         "apply_patches" => {
             session.apply_patches()?;
             Ok("patches applied".to_string())
         }
-        "submit_main_script_patch" => {
-            session.submit_main_script_patch(
-                args[0].as_str(),
-                args[1].as_str(),
-                args[2].as_str(),
+        "submit_main_script_patch" | "dry_run_main_script_patch" => {
+            let plan = session.submit_main_script_patch(
+                arg(&args, 0)?,
+                arg(&args, 1)?,
+                arg(&args, 2)?,
+                args.get(3)
+                    .map(|owner| owner.as_str())
+                    .filter(|owner| !owner.is_empty()),
+                args.get(4).is_some_and(|all_entries| all_entries == "true"),
+                command == "dry_run_main_script_patch",
+            )?;
+            serde_json::to_string(&plan).map_err(|_| WalError::SerializationError)
+        }
+        "submit_stylesheet_patch" | "dry_run_stylesheet_patch" => {
+            let plan = session.submit_stylesheet_patch(
+                arg(&args, 0)?,
+                arg(&args, 1)?,
+                arg(&args, 2)?,
+                args.get(3)
+                    .map(|owner| owner.as_str())
+                    .filter(|owner| !owner.is_empty()),
+                command == "dry_run_stylesheet_patch",
             )?;
-            Ok("main script patch submitted".to_string())
+            serde_json::to_string(&plan).map_err(|_| WalError::SerializationError)
+        }
+        "submit_html_patch" | "dry_run_html_patch" => {
+            let plan = session.submit_html_patch(
+                arg(&args, 0)?,
+                arg(&args, 1)?,
+                arg(&args, 2)?,
+                args.get(3)
+                    .map(|owner| owner.as_str())
+                    .filter(|owner| !owner.is_empty()),
+                command == "dry_run_html_patch",
+            )?;
+            serde_json::to_string(&plan).map_err(|_| WalError::SerializationError)
         }
         "backup_exists" => {
-            let exists = session.backup_exists(args[0].as_str())?;
+            let exists = session.backup_exists(arg(&args, 0)?)?;
             Ok(if exists { "true" } else { "false" }.to_string())
         }
         "restore_backup" => {
-            session.restore_backup(args[0].as_str())?;
+            session.restore_backup(arg(&args, 0)?)?;
             Ok("backup restored".to_string())
         }
         "create_backup" => {
-            session.create_backup(args[0].as_str())?;
+            session.create_backup(arg(&args, 0)?)?;
             Ok("backup created".to_string())
         }
         "find_backups" => {
@@ -99,20 +333,304 @@ pub fn interpret_command(session_id: &str, command: &str, args: Vec<String>) ->
             session.restore_all_backups()?;
             Ok("all backups restored".to_string())
         }
+        "list_queued_patches" => {
+            let queued = session.list_queued_patches();
+            serde_json::to_string(&queued).map_err(|_| WalError::SerializationError)
+        }
+        "remove_queued_patch" => {
+            session.remove_queued_patch(arg(&args, 0)?, arg(&args, 1)?)?;
+            Ok("queued patch removed".to_string())
+        }
+        "clear_queued_patches" => {
+            session.clear_queued_patches(arg(&args, 0)?)?;
+            Ok("queued patches cleared".to_string())
+        }
+        "preflight" => {
+            let problems = session.preflight()?;
+            serde_json::to_string(&problems).map_err(|_| WalError::SerializationError)
+        }
+        "export_patches" => {
+            let manifest = session.export_patches(arg(&args, 0)?, arg(&args, 1)?)?;
+            serde_json::to_string(&manifest).map_err(|_| WalError::SerializationError)
+        }
         _ => Err(WalError::InvalidCommandError),
     }
 }
 
+/// 一次提交展开出的补丁目标
+#[derive(Clone, Debug, Default, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PatchPlan {
+    /// 会写入补丁的内部文件
+    pub targets: Vec<String>,
+    /// glob 匹配到、但不适合打补丁的路径
+    pub skipped: Vec<SkippedTarget>,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SkippedTarget {
+    pub path: String,
+    pub reason: String,
+}
+
+impl PatchPlan {
+    fn single(inner_path: &str) -> Self {
+        Self {
+            targets: vec![inner_path.to_string()],
+            skipped: Vec::new(),
+        }
+    }
+
+    fn skip(&mut self, path: &str, reason: impl std::fmt::Debug) {
+        self.skipped.push(SkippedTarget {
+            path: path.to_string(),
+            reason: format!("{:?}", reason),
+        });
+    }
+}
+
+/// 是否按 glob 展开：确切路径优先，`pages/[id].js` 这类名字本身带通配符的文件存在时按原样处理
+fn expands_as_glob(file: &LarkAsarFile, path: &str) -> bool {
+    is_glob(path) && file.resolve(path).is_err()
+}
+
+/// 展开 glob 匹配的内部文件；扩展名不在 extensions 中或无法打补丁的匹配记入 skipped
+fn plan_file_patches(
+    file: &LarkAsarFile,
+    pattern: &str,
+    extensions: &[&str],
+    patch: impl Fn(&AsarPath) -> WalResult<PatchUnit>,
+) -> WalResult<(Vec<PatchUnit>, PatchPlan)> {
+    let glob = Glob::new(pattern)?;
+    let mut patches = Vec::new();
+    let mut plan = PatchPlan::default();
+    for path in file.files() {
+        if !glob.matches(path) {
+            continue;
+        }
+        if !path
+            .extension()
+            .is_some_and(|extension| extensions.contains(&extension))
+        {
+            plan.skip(
+                path.as_str(),
+                format!("extension is not one of {:?}", extensions),
+            );
+            continue;
+        }
+        match patch(path) {
+            Ok(patch) => {
+                plan.targets.push(path.to_string());
+                patches.push(patch);
+            }
+            Err(e) => plan.skip(path.as_str(), e),
+        }
+    }
+    if plan.targets.is_empty() && plan.skipped.is_empty() {
+        return Err(WalError::PatternNotFoundError);
+    }
+    Ok((patches, plan))
+}
+
+/// 含 index.html、且匹配 subject glob 的页面目录
+fn glob_subjects(file: &LarkAsarFile, subject: &str) -> WalResult<Vec<AsarPath>> {
+    let glob = Glob::new(subject)?;
+    let subjects = file
+        .files()
+        .filter(|path| path.file_name() == "index.html")
+        .filter_map(|path| path.parent())
+        .filter(|subject| glob.matches(subject))
+        .collect::<Vec<_>>();
+    if subjects.is_empty() {
+        return Err(WalError::PatternNotFoundError);
+    }
+    Ok(subjects)
+}
+
+fn plan_main_script_patches(
+    file: &LarkAsarFile,
+    subject: &str,
+    script: &str,
+    all_entries: bool,
+) -> WalResult<Vec<PatchUnit>> {
+    let inner_paths = if all_entries {
+        file.get_entry_script_paths(subject)?
+    } else {
+        vec![file.get_main_script_path(subject)?]
+    };
+    inner_paths
+        .iter()
+        .map(|inner_path| file.patch_script(inner_path, script))
+        .collect()
+}
+
+/// 排队补丁预览保留的字符数
+const QUEUED_PATCH_PREVIEW_CHARS: usize = 240;
+
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueuedPatch {
+    pub inner_path: String,
+    /// 打完补丁后的文件大小
+    pub size: usize,
+    pub owner: Option<String>,
+    /// 注入代码块的开头部分
+    pub preview: String,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueuedAsarPatches {
+    pub asar_path: String,
+    pub patches: Vec<QueuedPatch>,
+}
+
+/// 会话读写的目标
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase", tag = "kind", content = "value")]
+pub enum SessionTarget {
+    /// 检测到的客户端安装，值为 LarkVariant::id
+    Installed(String),
+    /// 指定的目录，用于离线准备补丁或在拷贝出来的飞书目录上测试
+    Directory(String),
+}
+
+fn apply_workers(total_asars: usize) -> usize {
+    let parallelism = thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1);
+    parallelism.min(MAX_APPLY_WORKERS).min(total_asars).max(1)
+}
+
+/// 备份目标并把打好补丁的 asar 写到临时文件，返回从源 asar 读取和写入的字节数
+fn stage_asar(
+    file: &LarkAsarFile,
+    patches: &HashSet<PatchUnit>,
+    mut progress: ApplyProgress,
+    token: &CancellationToken,
+    events: &mpsc::Sender<ApplyEvent>,
+) -> WalResult<(u64, u64)> {
+    let report = |progress: &ApplyProgress| {
+        let _ = events.send(ApplyEvent::Progress(progress.clone()));
+    };
+    // 备份与重建头部都不看令牌，每步之间检查一次，取消后不再开始下一步
+    let check_cancelled = || {
+        if token.is_cancelled() {
+            return Err(WalError::ApplyCancelledError);
+        }
+        Ok(())
+    };
+    report(&progress);
+    let target_file = file.asar_absolute_path();
+    create_backup(target_file)?;
+    check_cancelled()?;
+
+    progress.phase = ApplyPhase::Rewrite;
+    report(&progress);
+    let patched = PatchedAsar::build(file, patches)?;
+    check_cancelled()?;
+
+    progress.phase = ApplyPhase::Write;
+    report(&progress);
+    let temp_file = make_temp_path(target_file);
+    let mut on_write = |read: u64, written: u64| {
+        progress.bytes_read = read;
+        progress.bytes_written = written;
+        report(&progress);
+    };
+    let result = write_asar(&temp_file, &patched, token, &mut on_write);
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_file);
+    }
+    result
+}
+
+/// 依次把临时文件换到目标位置，原文件先改名留作回退。
+/// 任一替换失败时把已替换的全部换回，这批 asar 要么全部更新，要么都不变。
+fn swap_staged(targets: &[String]) -> WalResult<()> {
+    let mut swapped = Vec::new();
+    let mut failed = false;
+    for target in targets {
+        let previous = make_previous_path(target);
+        if fs::rename(target, &previous).is_err() {
+            failed = true;
+            break;
+        }
+        if fs::rename(make_temp_path(target), target).is_err() {
+            let _ = fs::rename(&previous, target);
+            failed = true;
+            break;
+        }
+        swapped.push(target);
+    }
+
+    if failed {
+        error!("failed to swap staged asars, rolling back");
+        for target in swapped.iter().rev() {
+            let _ = fs::rename(make_previous_path(target), target);
+        }
+        for target in targets {
+            let _ = fs::remove_file(make_temp_path(target));
+        }
+        return Err(WalError::IoError);
+    }
+    for target in targets {
+        let _ = fs::remove_file(make_previous_path(target));
+    }
+    Ok(())
+}
+
+fn write_asar(
+    path: &str,
+    patched: &PatchedAsar,
+    token: &CancellationToken,
+    on_write: &mut dyn FnMut(u64, u64),
+) -> WalResult<(u64, u64)> {
+    let file = fs::File::create(path).map_err(|_| WalError::IoError)?;
+    let mut progress_writer = ProgressWriter::new(io::BufWriter::new(file), token, on_write);
+    for (data, from_source) in patched.segments() {
+        for chunk in data.chunks(COPY_CHUNK_SIZE) {
+            if from_source {
+                progress_writer.record_read(chunk.len() as u64);
+            }
+            progress_writer
+                .write_all(chunk)
+                .map_err(|_| WalError::IoError)?;
+        }
+    }
+    progress_writer.flush().map_err(|_| WalError::IoError)?;
+    Ok((progress_writer.read(), progress_writer.written()))
+}
+
+/// 一个 asar 上排队的补丁
+struct QueuedAsar {
+    /// 首次提交时传入的路径，用于展示和重新映射
+    asar_path: String,
+    /// 计算补丁时的源文件；应用前核对，不一致说明补丁已过期
+    stamp: AsarStamp,
+    patches: HashSet<PatchUnit>,
+}
+
 pub struct LarkSession {
     id: String,
-    patch_map: BTreeMap<LarkAsarFile, HashSet<PatchUnit>>,
+    target: SessionTarget,
+    last_used: Instant,
+    /// 规范化的目标绝对路径 -> 排队中的补丁。同一文件的不同写法
+    /// （相对、绝对、分隔符或大小写不同）落在同一项里，应用时只会写一次
+    patch_map: BTreeMap<String, QueuedAsar>,
+    /// 已映射的 asar，键同 patch_map，按修改时间、大小判断是否需要重新映射
+    asar_cache: BTreeMap<String, LarkAsarFile>,
 }
 
 impl LarkSession {
-    pub fn new() -> Self {
+    pub fn new(target: SessionTarget) -> Self {
         Self {
             id: uuid::Uuid::new_v4().to_string(),
+            target,
+            last_used: Instant::now(),
             patch_map: BTreeMap::new(),
+            asar_cache: BTreeMap::new(),
         }
     }
 
@@ -120,83 +638,600 @@ impl LarkSession {
         &self.id
     }
 
+    pub fn touch(&mut self) {
+        self.last_used = Instant::now();
+    }
+
+    pub fn last_used(&self) -> Instant {
+        self.last_used
+    }
+
+    /// 会话映射的 asar 与补丁数据总量
+    pub fn memory_usage(&self) -> usize {
+        let mapped = self
+            .asar_cache
+            .values()
+            .map(|file| file.size() as usize)
+            .sum::<usize>();
+        let patches = self
+            .patch_map
+            .values()
+            .flat_map(|queued| queued.patches.iter().map(|p| p.data.len()))
+            .sum::<usize>();
+        mapped + patches
+    }
+
+    pub fn asar_infos(&self) -> Vec<SessionAsarInfo> {
+        self.patch_map
+            .iter()
+            .map(|(key, queued)| SessionAsarInfo {
+                asar_path: queued.asar_path.clone(),
+                patch_count: queued.patches.len(),
+                asar_bytes: self
+                    .asar_cache
+                    .get(key)
+                    .map(|file| file.size() as usize)
+                    .unwrap_or(0),
+            })
+            .collect()
+    }
+
+    pub fn target(&self) -> &SessionTarget {
+        &self.target
+    }
+
+    /// 会话的基准目录：安装会话为当前版本目录，目录会话为绑定的目录
+    pub fn base_path(&self) -> WalResult<String> {
+        match &self.target {
+            SessionTarget::Installed(variant) => {
+                lark_variant_session(Some(variant), |helper| helper.locate())
+            }
+            SessionTarget::Directory(path) => Ok(path.clone()),
+        }
+    }
+
+    /// 对目标 asar 所在的目录加锁，apply、restore、create_backup 期间持有。
+    /// 两种会话都锁基准目录规范化后的路径，用不同写法指向同一版本目录的会话拿到的是同一把锁。
+    fn lock_target(&self) -> WalResult<InstallDirLock> {
+        let dir = fs::canonicalize(self.base_path()?).map_err(|_| WalError::IoError)?;
+        lock_install_dir(&dir.to_string_lossy(), &self.id)
+    }
+
+    /// 相对路径按基准目录解析，绝对路径原样使用
+    fn resolve_path(&self, path: &str) -> WalResult<String> {
+        if Path::new(path).is_absolute() {
+            return Ok(path.to_string());
+        }
+        join_components(&[&self.base_path()?, path])
+    }
+
+    /// patch_map 与 asar_cache 的键：规范化后的目标绝对路径
+    fn target_key(&self, asar_path: &str) -> WalResult<String> {
+        Ok(normalize_path(&self.resolve_path(asar_path)?))
+    }
+
+    /// 取缓存的 asar；未缓存、基准目录变化或文件已被修改时重新映射
+    fn load_asar(&mut self, asar_path: &str) -> WalResult<&LarkAsarFile> {
+        let base_path = self.base_path()?;
+        let key = self.target_key(asar_path)?;
+        let fresh = self
+            .asar_cache
+            .get(&key)
+            .is_some_and(|file| file.base_path() == base_path && !file.is_stale());
+        if !fresh {
+            // 先释放旧映射再打开
+            self.asar_cache.remove(&key);
+            let file = LarkAsarFile::new(base_path, asar_path.to_owned())?;
+            self.asar_cache.insert(key.clone(), file);
+        }
+        Ok(&self.asar_cache[&key])
+    }
+
+    /// 映射排队的 asar，并确认它仍是提交补丁时的那份文件；
+    /// 客户端更新或文件被改动后返回 StaleQueueError，而不是把旧内容拼进新文件
+    fn load_queued_asar(&mut self, key: &str) -> WalResult<&LarkAsarFile> {
+        let queued = self
+            .patch_map
+            .get(key)
+            .ok_or(WalError::NoQueuedPatchesError)?;
+        let (asar_path, stamp) = (queued.asar_path.clone(), queued.stamp.clone());
+        let file = self.load_asar(&asar_path)?;
+        if file.stamp() != stamp {
+            warn!("queued patches for {} are stale", asar_path);
+            return Err(WalError::StaleQueueError(asar_path));
+        }
+        Ok(file)
+    }
+
+    /// 释放所有映射。覆盖 asar 之前必须调用，否则 Windows 上无法替换被映射的文件
+    fn release_asars(&mut self) {
+        self.asar_cache.clear();
+    }
+
+    /// 释放本会话与其他会话在同一目录下的映射，修改文件前调用
+    fn release_target_asars(&mut self) -> WalResult<()> {
+        self.release_asars();
+        release_shared_asars(&self.base_path()?)
+    }
+
+    /// inner_path 可以是 glob，没有同名文件时展开为所有匹配的脚本，不适合打补丁的匹配跳过；
+    /// dry_run 为 true 时只返回展开结果，不排队
     pub fn submit_patch(
         &mut self,
         asar_path: &str,
         inner_path: &str,
         script: &str,
-    ) -> WalResult<()> {
-        lark_helper_session(|helper| {
-            let base_path = helper.locate()?;
-            let file = LarkAsarFile::new(base_path, asar_path.to_owned())?;
-            let patch = file.patch_script(&PathBuf::from(inner_path), script)?;
-            if self.patch_map.contains_key(&file) {
-                self.patch_map.get_mut(&file).unwrap().replace(patch);
-            } else {
-                let mut set = HashSet::new();
-                set.insert(patch);
-                self.patch_map.insert(file, set);
-            }
-            Ok(())
-        })
+        owner: Option<&str>,
+        dry_run: bool,
+    ) -> WalResult<PatchPlan> {
+        let file = self.load_asar(asar_path)?;
+        let (patches, plan) = if expands_as_glob(file, inner_path) {
+            plan_file_patches(file, inner_path, &SCRIPT_EXTENSIONS, |path| {
+                file.patch_script(path, script)
+            })?
+        } else {
+            let path = file.resolve(inner_path)?;
+            let patch = file.patch_script(&path, script)?;
+            (vec![patch], PatchPlan::single(path.as_str()))
+        };
+        if !dry_run {
+            self.queue_patches(asar_path, patches, owner)?;
+        }
+        Ok(plan)
     }
 
+    /// 按 subject 目录下 index.html 找到入口脚本并提交补丁；
+    /// all_entries 为 true 时给页面引用的每个脚本都提交同一补丁。
+    /// subject 可以是 glob，没有同名页面时匹配含 index.html 的目录，找不到入口的目录跳过
     pub fn submit_main_script_patch(
         &mut self,
         asar_path: &str,
         subject: &str,
         script: &str,
+        owner: Option<&str>,
+        all_entries: bool,
+        dry_run: bool,
+    ) -> WalResult<PatchPlan> {
+        let file = self.load_asar(asar_path)?;
+        let mut patches = Vec::new();
+        let mut plan = PatchPlan::default();
+        if expands_as_glob(file, &format!("{}/index.html", subject)) {
+            for subject in glob_subjects(file, subject)? {
+                match plan_main_script_patches(file, subject.as_str(), script, all_entries) {
+                    Ok(planned) => patches.extend(planned),
+                    Err(e) => plan.skip(subject.as_str(), e),
+                }
+            }
+        } else {
+            patches = plan_main_script_patches(file, subject, script, all_entries)?;
+        }
+        plan.targets = patches.iter().map(|patch| patch.path.to_string()).collect();
+        if !dry_run {
+            self.queue_patches(asar_path, patches, owner)?;
+        }
+        Ok(plan)
+    }
+
+    /// 把 css 追加到样式表末尾；inner_path 可以是 glob，没有同名文件时只展开到 .css 文件
+    pub fn submit_stylesheet_patch(
+        &mut self,
+        asar_path: &str,
+        inner_path: &str,
+        css: &str,
+        owner: Option<&str>,
+        dry_run: bool,
+    ) -> WalResult<PatchPlan> {
+        let file = self.load_asar(asar_path)?;
+        let (patches, plan) = if expands_as_glob(file, inner_path) {
+            plan_file_patches(file, inner_path, &STYLESHEET_EXTENSIONS, |path| {
+                file.patch_stylesheet(path, css)
+            })?
+        } else {
+            let path = file.resolve(inner_path)?;
+            let patch = file.patch_stylesheet(&path, css)?;
+            (vec![patch], PatchPlan::single(path.as_str()))
+        };
+        if !dry_run {
+            self.queue_patches(asar_path, patches, owner)?;
+        }
+        Ok(plan)
+    }
+
+    /// 把 html 插入 subject 目录下 index.html 的 `</head>` 之前；
+    /// subject 可以是 glob，没有同名页面时匹配含 index.html 的目录
+    pub fn submit_html_patch(
+        &mut self,
+        asar_path: &str,
+        subject: &str,
+        html: &str,
+        owner: Option<&str>,
+        dry_run: bool,
+    ) -> WalResult<PatchPlan> {
+        let file = self.load_asar(asar_path)?;
+        let mut patches = Vec::new();
+        let mut plan = PatchPlan::default();
+        if expands_as_glob(file, &format!("{}/index.html", subject)) {
+            for subject in glob_subjects(file, subject)? {
+                match file.patch_html(&subject.join("index.html"), html) {
+                    Ok(patch) => patches.push(patch),
+                    Err(e) => plan.skip(subject.as_str(), e),
+                }
+            }
+        } else {
+            let index = file.resolve(&format!("{}/index.html", subject))?;
+            patches.push(file.patch_html(&index, html)?);
+        }
+        plan.targets = patches.iter().map(|patch| patch.path.to_string()).collect();
+        if !dry_run {
+            self.queue_patches(asar_path, patches, owner)?;
+        }
+        Ok(plan)
+    }
+
+    /// 同一内部文件只保留最后提交的补丁。补丁由刚映射的 asar 算出，
+    /// 与已排队补丁的来源不同时拒绝混在一起
+    fn queue_patches(
+        &mut self,
+        asar_path: &str,
+        patches: Vec<PatchUnit>,
+        owner: Option<&str>,
     ) -> WalResult<()> {
-        lark_helper_session(|helper| {
-            let base_path = helper.locate()?;
-            let file = LarkAsarFile::new(base_path, asar_path.to_owned())?;
-            let inner_path = file.get_main_script_path(subject)?;
-            let patch = file.patch_script(&inner_path, script)?;
+        // glob 的匹配全被跳过时没有可写的内容，不能留下一个空条目让应用去重写原样的 asar
+        if patches.is_empty() {
+            return Err(WalError::PatternNotFoundError);
+        }
+        let key = self.target_key(asar_path)?;
+        let stamp = self
+            .asar_cache
+            .get(&key)
+            .map(|file| file.stamp())
+            .ok_or(WalError::AsarStructureError)?;
+        let queued = self.patch_map.entry(key).or_insert_with(|| QueuedAsar {
+            asar_path: asar_path.to_string(),
+            stamp: stamp.clone(),
+            patches: HashSet::new(),
+        });
+        if queued.stamp != stamp {
+            return Err(WalError::StaleQueueError(queued.asar_path.clone()));
+        }
+        for mut patch in patches {
+            patch.owner = owner.map(|owner| owner.to_string());
+            queued.patches.replace(patch);
+        }
+        Ok(())
+    }
+
+    pub fn list_queued_patches(&self) -> Vec<QueuedAsarPatches> {
+        self.patch_map
+            .values()
+            .map(|queued| {
+                let mut patches = queued
+                    .patches
+                    .iter()
+                    .map(|patch| QueuedPatch {
+                        inner_path: patch.path.to_string(),
+                        size: patch.data.len(),
+                        owner: patch.owner.clone(),
+                        preview: patch
+                            .script
+                            .chars()
+                            .take(QUEUED_PATCH_PREVIEW_CHARS)
+                            .collect(),
+                    })
+                    .collect::<Vec<_>>();
+                patches.sort_by(|a, b| a.inner_path.cmp(&b.inner_path));
+                QueuedAsarPatches {
+                    asar_path: queued.asar_path.clone(),
+                    patches,
+                }
+            })
+            .collect()
+    }
+
+    /// 撤回某个 asar 中一个内部文件的补丁；asar 没有剩余补丁时一并释放
+    pub fn remove_queued_patch(&mut self, asar_path: &str, inner_path: &str) -> WalResult<()> {
+        let key = self.target_key(asar_path)?;
+        let queued = self
+            .patch_map
+            .get_mut(&key)
+            .ok_or(WalError::NoQueuedPatchesError)?;
+        let inner_path = AsarPath::parse(inner_path)?;
+        let count = queued.patches.len();
+        queued.patches.retain(|patch| patch.path != inner_path);
+        if queued.patches.len() == count {
+            return Err(WalError::NoQueuedPatchesError);
+        }
+        if queued.patches.is_empty() {
+            self.patch_map.remove(&key);
+            self.asar_cache.remove(&key);
+        }
+        Ok(())
+    }
 
-            if self.patch_map.contains_key(&file) {
-                self.patch_map.get_mut(&file).unwrap().insert(patch);
+    /// 清空某个 asar 的全部补丁
+    pub fn clear_queued_patches(&mut self, asar_path: &str) -> WalResult<()> {
+        let key = self.target_key(asar_path)?;
+        if self.patch_map.remove(&key).is_none() {
+            return Err(WalError::NoQueuedPatchesError);
+        }
+        self.asar_cache.remove(&key);
+        Ok(())
+    }
+
+    pub fn apply_patches(&mut self) -> WalResult<()> {
+        self.apply_patches_with_progress(&CancellationToken::new(), &mut |_| {})?;
+        Ok(())
+    }
+
+    /// 写入前逐个检查排队的 asar：客户端未运行、文件可写且未被占用、
+    /// 磁盘放得下备份与新 asar。返回所有阻塞问题，为空时可以应用。
+    pub fn preflight(&mut self) -> WalResult<Vec<PreflightProblem>> {
+        let mut problems = Vec::new();
+        if let SessionTarget::Installed(variant) = &self.target {
+            let executable = lark_variant_session(Some(variant), |helper| {
+                Ok(helper.variant().executable.clone())
+            })?;
+            if is_process_running(&executable) {
+                problems.push(PreflightProblem::LarkRunning {
+                    variant: variant.clone(),
+                });
+            }
+        }
+
+        // 会话自己和其他会话的映射都会让独占打开失败，检查前先释放
+        self.release_target_asars()?;
+        let queued = self
+            .patch_map
+            .iter()
+            .map(|(key, queued)| (key.clone(), queued.asar_path.clone()))
+            .collect::<Vec<_>>();
+        let mut required = 0;
+        for (key, asar_path) in queued {
+            let target = self.resolve_path(&asar_path)?;
+            if !Path::new(&target).is_file() {
+                problems.push(PreflightProblem::AsarMissing { asar_path });
+                continue;
+            }
+            if !is_writable(&target) {
+                problems.push(PreflightProblem::NotWritable { asar_path });
+                continue;
+            }
+            if is_in_use(&target) {
+                problems.push(PreflightProblem::FileInUse { asar_path });
+                continue;
+            }
+            self.load_queued_asar(&key)?;
+            let file = &self.asar_cache[&key];
+            let patched = PatchedAsar::build(file, &self.patch_map[&key].patches)?;
+            let backup_size = if backup_exists(&target)? {
+                0
             } else {
-                let mut set = HashSet::new();
-                set.insert(patch);
-                self.patch_map.insert(file, set);
+                file.size()
+            };
+            required += backup_size + patched.total_size();
+        }
+
+        if required > 0 {
+            let available = free_disk_space(&self.base_path()?)?;
+            if available < required {
+                problems.push(PreflightProblem::InsufficientSpace {
+                    required,
+                    available,
+                });
             }
-            Ok(())
-        })
+        }
+        Ok(problems)
     }
 
-    pub fn apply_patches(&self) -> WalResult<()> {
-        for (file, patches) in self.patch_map.iter() {
-            let reader = file.create_reader()?;
-            let patched_writer = AsarWriter::from_reader_with_patches(&reader, patches)?;
-            let target_file = file.asar_absolute_path();
-            create_backup(target_file)?;
-            let file = fs::File::create(target_file).map_err(|_| WalError::IoError)?;
-            patched_writer
-                .finalize(file)
-                .map_err(|_| WalError::IoError)?;
+    /// 在有限的工作线程上并行地备份、重写各个 asar 并写到临时文件，
+    /// 全部成功后再按顺序替换目标；替换中途失败会把已替换的换回。
+    /// 因此取消或出错时这批 asar 全部保持原样。进度写入应用日志，
+    /// 进程中途退出时下次启动据此补完或回退。
+    /// 完成事件与汇总都按 asar 顺序给出；重写时复用打开 asar 时解析的头部，不再重复解析。
+    pub fn apply_patches_with_progress(
+        &mut self,
+        token: &CancellationToken,
+        report: &mut dyn FnMut(ApplyEvent),
+    ) -> WalResult<ApplySummary> {
+        let started = Instant::now();
+        let lock = self.lock_target()?;
+        let problems = self.preflight()?;
+        if !problems.is_empty() {
+            warn!("preflight failed: {:?}", problems);
+            return Err(WalError::PreflightError);
         }
-        Ok(())
+        let keys = self.patch_map.keys().cloned().collect::<Vec<_>>();
+        let asar_paths = self
+            .patch_map
+            .values()
+            .map(|queued| queued.asar_path.clone())
+            .collect::<Vec<_>>();
+        let total_asars = keys.len();
+        let mut summary = ApplySummary::default();
+        report(ApplyEvent::Started { total_asars });
+
+        for key in &keys {
+            self.load_queued_asar(key)?;
+        }
+        let targets = keys
+            .iter()
+            .map(|key| self.asar_cache[key].asar_absolute_path().to_string())
+            .collect::<Vec<_>>();
+
+        let mut journal = ApplyJournal::begin(&self.id, lock.dir(), &targets)?;
+
+        // 任一 asar 失败时让其余工作线程尽快停下
+        let worker_token = token.child_token();
+        let next = AtomicUsize::new(0);
+        let (events, received) = mpsc::channel();
+        let mut staged = thread::scope(|scope| {
+            let (next, worker_token) = (&next, &worker_token);
+            let (keys, asar_paths) = (&keys, &asar_paths);
+            let (asar_cache, patch_map) = (&self.asar_cache, &self.patch_map);
+            let handles = (0..apply_workers(total_asars))
+                .map(|_| {
+                    let events = events.clone();
+                    scope.spawn(move || {
+                        let mut staged = Vec::new();
+                        loop {
+                            let asar_index = next.fetch_add(1, Ordering::Relaxed);
+                            if asar_index >= total_asars || worker_token.is_cancelled() {
+                                break;
+                            }
+                            let asar_path = &asar_paths[asar_index];
+                            let progress = ApplyProgress {
+                                asar_path: asar_path.clone(),
+                                asar_index,
+                                total_asars,
+                                phase: ApplyPhase::Backup,
+                                bytes_read: 0,
+                                bytes_written: 0,
+                            };
+                            let key = &keys[asar_index];
+                            // panic 只算作这个 asar 失败，不能让整个线程的结果丢失
+                            let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                                stage_asar(
+                                    &asar_cache[key],
+                                    &patch_map[key].patches,
+                                    progress,
+                                    worker_token,
+                                    &events,
+                                )
+                            }))
+                            .unwrap_or_else(|_| {
+                                error!("staging {} panicked", asar_path);
+                                Err(WalError::WorkerPanicError)
+                            });
+                            // 被其他线程的失败或用户取消打断的不算作失败原因
+                            if result.is_err() && worker_token.is_cancelled() {
+                                continue;
+                            }
+                            if result.is_err() {
+                                worker_token.cancel();
+                            }
+                            staged.push((asar_index, result));
+                        }
+                        staged
+                    })
+                })
+                .collect::<Vec<_>>();
+            drop(events);
+            for event in received {
+                report(event);
+            }
+            let mut staged = Vec::new();
+            for handle in handles {
+                match handle.join() {
+                    Ok(results) => staged.extend(results),
+                    // 结果缺失的 asar 在下面按 WorkerPanicError 处理
+                    Err(_) => error!("apply worker panicked"),
+                }
+            }
+            staged
+        });
+        staged.sort_by_key(|(asar_index, _)| *asar_index);
+
+        let all_staged =
+            staged.len() == total_asars && staged.iter().all(|(_, result)| result.is_ok());
+        if !all_staged || token.is_cancelled() {
+            for target in &targets {
+                let _ = fs::remove_file(make_temp_path(target));
+            }
+            journal.finish();
+            if token.is_cancelled() {
+                summary.cancelled = true;
+                summary.elapsed_ms = started.elapsed().as_millis() as u64;
+                return Ok(summary);
+            }
+            let error = staged
+                .into_iter()
+                .find_map(|(_, result)| result.err())
+                .unwrap_or(WalError::WorkerPanicError);
+            return Err(error);
+        }
+
+        // 替换前必须释放映射，否则 Windows 上无法覆盖被映射的文件；
+        // 其他会话可能在 preflight 之后又映射了同一文件
+        if let Err(e) = self.release_target_asars() {
+            for target in &targets {
+                let _ = fs::remove_file(make_temp_path(target));
+            }
+            journal.finish();
+            return Err(e);
+        }
+        if let Err(e) = journal.mark_staged() {
+            for target in &targets {
+                let _ = fs::remove_file(make_temp_path(target));
+            }
+            journal.finish();
+            return Err(e);
+        }
+        let swapped = swap_staged(&targets);
+        journal.finish();
+        swapped?;
+        // 补丁已写入，目标的时间戳也变了，留在队列里只会让下次 apply 报 StaleQueueError
+        for key in &keys {
+            self.patch_map.remove(key);
+        }
+
+        for (asar_index, result) in staged {
+            let (read, written) = result?;
+            report(ApplyEvent::Progress(ApplyProgress {
+                asar_path: asar_paths[asar_index].clone(),
+                asar_index,
+                total_asars,
+                phase: ApplyPhase::Done,
+                bytes_read: read,
+                bytes_written: written,
+            }));
+            summary.applied.push(asar_paths[asar_index].clone());
+            summary.bytes_read += read;
+            summary.bytes_written += written;
+        }
+
+        summary.elapsed_ms = started.elapsed().as_millis() as u64;
+        Ok(summary)
+    }
+
+    /// 把某个 asar 已提交的补丁写到指定位置，不修改源文件
+    pub fn export_patches(
+        &mut self,
+        asar_path: &str,
+        output_path: &str,
+    ) -> WalResult<ExportManifest> {
+        let key = self.target_key(asar_path)?;
+        self.load_queued_asar(&key)?;
+        export::export_patched_asar(
+            &self.asar_cache[&key],
+            &self.patch_map[&key].patches,
+            output_path,
+        )
     }
 
     pub fn backup_exists(&self, path: &str) -> WalResult<bool> {
-        file::backup::backup_exists(path)
+        file::backup::backup_exists(&self.resolve_path(path)?)
     }
 
-    pub fn restore_backup(&self, path: &str) -> WalResult<()> {
-        file::backup::restore_backup(path)
+    pub fn restore_backup(&mut self, path: &str) -> WalResult<()> {
+        let _lock = self.lock_target()?;
+        self.release_target_asars()?;
+        file::backup::restore_backup(&self.resolve_path(path)?)
     }
 
     pub fn create_backup(&self, path: &str) -> WalResult<()> {
-        file::backup::create_backup(path)
+        let _lock = self.lock_target()?;
+        file::backup::create_backup(&self.resolve_path(path)?)
     }
 
     pub fn find_backups(&self) -> WalResult<Vec<String>> {
-        let base_path = lark_helper_session(|helper| helper.locate())?;
+        let base_path = self.base_path()?;
         file::backup::find_backups_recursively(&base_path)
     }
 
-    pub fn restore_all_backups(&self) -> WalResult<()> {
-        let base_path = lark_helper_session(|helper| helper.locate())?;
+    pub fn restore_all_backups(&mut self) -> WalResult<()> {
+        let _lock = self.lock_target()?;
+        self.release_target_asars()?;
+        let base_path = self.base_path()?;
         file::backup::restore_all_backups_recursively(&base_path)
     }
 }
@@ -0,0 +1,102 @@
+use std::{
+    backtrace::Backtrace,
+    fs,
+    panic::{self, PanicHookInfo},
+    path::{Path, PathBuf},
+    sync::{LazyLock, Mutex},
+    thread,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::error::{WalError, WalResult};
+
+/// 启动时读到的上次崩溃，供前端在订阅事件之前错过时查询
+static PREVIOUS_CRASH: LazyLock<Mutex<Option<CrashReport>>> = LazyLock::new(|| Mutex::new(None));
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CrashReport {
+    /// Unix 时间戳（毫秒）
+    pub timestamp: u64,
+    pub thread: String,
+    pub message: String,
+    /// panic 发生的源码位置，`file:line:column`
+    pub location: Option<String>,
+    pub backtrace: String,
+}
+
+/// 本次运行的崩溃记录：<app_data>/crash.json，下次启动时读取
+pub fn crash_file(app_data_dir: &Path) -> PathBuf {
+    app_data_dir.join("crash.json")
+}
+
+/// 读取后保留的上一次崩溃：<app_data>/crash.last.json，随诊断包导出
+pub fn last_crash_file(app_data_dir: &Path) -> PathBuf {
+    app_data_dir.join("crash.last.json")
+}
+
+/// 记录 panic 的现场并通知 notify，然后交给原有的钩子输出。
+/// 只有会让进程退出的 panic 才写崩溃文件：主线程 panic 会拆掉事件循环，panic=abort 时任何 panic 都会终止进程；
+/// 其他线程的 panic 会被 catch_unwind 或 JoinHandle 接住，进程继续运行，下次启动不必提示。
+/// 这样不依赖退出时清理，插件或更新器直接结束进程时也不会留下误报。
+/// 钩子里不经过 logger：panic 可能正发生在持有日志锁的时候。
+pub fn install_panic_hook(app_data_dir: PathBuf, notify: Box<dyn Fn(&CrashReport) + Send + Sync>) {
+    let previous = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        let report = make_report(info);
+        if is_fatal_panic() {
+            if let Ok(content) = serde_json::to_string_pretty(&report) {
+                let _ = fs::write(crash_file(&app_data_dir), content);
+            }
+        }
+        notify(&report);
+        previous(info);
+    }));
+}
+
+fn is_fatal_panic() -> bool {
+    cfg!(panic = "abort") || thread::current().name() == Some("main")
+}
+
+/// 启动时检查上次运行是否崩溃；有记录时移到 crash.last.json，避免重复提示
+pub fn take_previous_crash(app_data_dir: &Path) -> Option<CrashReport> {
+    let path = crash_file(app_data_dir);
+    let content = fs::read_to_string(&path).ok()?;
+    let _ = fs::rename(&path, last_crash_file(app_data_dir));
+    let report = serde_json::from_str::<CrashReport>(&content).ok()?;
+    if let Ok(mut crash) = PREVIOUS_CRASH.lock() {
+        *crash = Some(report.clone());
+    }
+    Some(report)
+}
+
+pub fn previous_crash() -> WalResult<Option<CrashReport>> {
+    let crash = PREVIOUS_CRASH.lock().map_err(|_| WalError::LockError)?;
+    Ok(crash.clone())
+}
+
+fn make_report(info: &PanicHookInfo) -> CrashReport {
+    let payload = info.payload();
+    let message = payload
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic payload".to_string());
+    CrashReport {
+        timestamp: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0),
+        thread: thread::current().name().unwrap_or("<unnamed>").to_string(),
+        message,
+        location: info.location().map(|location| {
+            format!(
+                "{}:{}:{}",
+                location.file(),
+                location.line(),
+                location.column()
+            )
+        }),
+        backtrace: Backtrace::force_capture().to_string(),
+    }
+}
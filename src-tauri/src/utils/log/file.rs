@@ -0,0 +1,84 @@
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    error::{WalError, WalResult},
+    utils::log::logger::LogRecord,
+};
+
+/// 单个日志文件的大小上限，超出后轮转
+const MAX_LOG_FILE_BYTES: u64 = 2 * 1024 * 1024;
+/// 保留的历史日志文件数（wal.1.log ~ wal.N.log）
+const MAX_LOG_FILES: usize = 5;
+
+/// 日志目录：<app_data>/logs
+pub fn logs_dir(app_data_dir: &Path) -> PathBuf {
+    app_data_dir.join("logs")
+}
+
+/// 当前日志文件与轮转出的历史文件，从新到旧
+pub fn log_files(app_data_dir: &Path) -> Vec<PathBuf> {
+    let dir = logs_dir(app_data_dir);
+    std::iter::once(dir.join("wal.log"))
+        .chain((1..=MAX_LOG_FILES).map(|index| dir.join(format!("wal.{}.log", index))))
+        .filter(|path| path.is_file())
+        .collect()
+}
+
+/// 按大小轮转的日志文件，每行一条 JSON 记录
+pub struct RotatingFile {
+    dir: PathBuf,
+    file: fs::File,
+    size: u64,
+}
+
+impl RotatingFile {
+    pub fn open(app_data_dir: &Path) -> WalResult<Self> {
+        let dir = logs_dir(app_data_dir);
+        fs::create_dir_all(&dir).map_err(|_| WalError::IoError)?;
+        let (file, size) = open_current(&dir)?;
+        Ok(Self { dir, file, size })
+    }
+
+    /// 写入失败时静默丢弃，日志不能影响正常流程
+    pub fn write_record(&mut self, record: &LogRecord) {
+        let Ok(mut line) = serde_json::to_vec(record) else {
+            return;
+        };
+        line.push(b'\n');
+        if self.size + line.len() as u64 > MAX_LOG_FILE_BYTES {
+            let _ = self.rotate();
+        }
+        if self.file.write_all(&line).is_ok() {
+            self.size += line.len() as u64;
+        }
+    }
+
+    fn rotate(&mut self) -> WalResult<()> {
+        let path = |index: usize| match index {
+            0 => self.dir.join("wal.log"),
+            _ => self.dir.join(format!("wal.{}.log", index)),
+        };
+        let _ = fs::remove_file(path(MAX_LOG_FILES));
+        for index in (0..MAX_LOG_FILES).rev() {
+            let _ = fs::rename(path(index), path(index + 1));
+        }
+        let (file, size) = open_current(&self.dir)?;
+        self.file = file;
+        self.size = size;
+        Ok(())
+    }
+}
+
+fn open_current(dir: &Path) -> WalResult<(fs::File, u64)> {
+    let file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(dir.join("wal.log"))
+        .map_err(|_| WalError::IoError)?;
+    let size = file.metadata().map(|m| m.len()).unwrap_or(0);
+    Ok((file, size))
+}
@@ -1,58 +1,220 @@
-use std::sync::{LazyLock, Mutex};
+use std::{
+    collections::{BTreeMap, VecDeque},
+    sync::{
+        atomic::{AtomicU64, AtomicU8, Ordering},
+        LazyLock, Mutex, MutexGuard, PoisonError,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
 
-use crate::error::{WalError, WalResult};
+use crate::{error::WalResult, utils::log::file::RotatingFile};
 
-pub type LogSubscriber = Box<dyn Fn(String) + Send + Sync>;
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, serde::Serialize, serde::Deserialize,
+)]
+#[serde(rename_all = "camelCase")]
+pub enum LogLevel {
+    Verbose,
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl LogLevel {
+    fn from_u8(value: u8) -> Self {
+        match value {
+            0 => Self::Verbose,
+            1 => Self::Debug,
+            2 => Self::Info,
+            3 => Self::Warn,
+            _ => Self::Error,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Verbose => "VERBOSE",
+            Self::Debug => "DEBUG",
+            Self::Info => "INFO",
+            Self::Warn => "WARN",
+            Self::Error => "ERROR",
+        }
+    }
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogRecord {
+    /// 进程内递增的序号，从 1 开始
+    pub seq: u64,
+    /// Unix 时间戳（毫秒）
+    pub timestamp: u64,
+    pub level: LogLevel,
+    /// 产生日志的模块路径
+    pub module: String,
+    pub message: String,
+    pub fields: BTreeMap<String, String>,
+}
+
+impl LogRecord {
+    /// `message key=value ...`
+    pub fn text(&self) -> String {
+        let mut text = self.message.clone();
+        for (key, value) in &self.fields {
+            text.push_str(&format!(" {}={}", key, value));
+        }
+        text
+    }
+
+    /// 单行文本：`LEVEL message key=value ...`
+    pub fn to_line(&self) -> String {
+        format!("{} {}", self.level.as_str(), self.text())
+    }
+}
+
+pub type LogSubscriber = Box<dyn Fn(&LogRecord) + Send + Sync>;
+
+/// 低于该级别的日志直接丢弃，对所有输出都生效
+static LEVEL: AtomicU8 = AtomicU8::new(LogLevel::Info as u8);
 
 static SUBSCRIBERS: LazyLock<Mutex<Vec<LogSubscriber>>> = LazyLock::new(|| {
-    Mutex::new(vec![Box::new(|message| {
-        println!("{}", message);
+    Mutex::new(vec![Box::new(|record| {
+        eprintln!("{}", record.to_line());
     })])
 });
 
+/// 内存中保留的最近日志条数
+const MAX_LOG_HISTORY: usize = 2000;
+
+static NEXT_SEQ: AtomicU64 = AtomicU64::new(1);
+
+/// 最近的日志，供晚于日志产生才订阅的前端补齐
+static HISTORY: LazyLock<Mutex<VecDeque<LogRecord>>> =
+    LazyLock::new(|| Mutex::new(VecDeque::with_capacity(MAX_LOG_HISTORY)));
+
+/// 日志文件，设置应用数据目录后才开始写
+static LOG_FILE: LazyLock<Mutex<Option<RotatingFile>>> = LazyLock::new(|| Mutex::new(None));
+
+/// 日志锁只保护追加型数据，持有者 panic 后内容仍然可用，直接接着用
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
 pub fn subscribe_log(subscriber: LogSubscriber) -> WalResult<()> {
-    SUBSCRIBERS
-        .lock()
-        .map_err(|_| WalError::LockError)?
-        .push(subscriber);
+    lock(&SUBSCRIBERS).push(subscriber);
+    Ok(())
+}
+
+pub fn set_log_level(level: LogLevel) {
+    LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+pub fn get_log_level() -> LogLevel {
+    LogLevel::from_u8(LEVEL.load(Ordering::Relaxed))
+}
+
+pub fn set_log_file(file: RotatingFile) -> WalResult<()> {
+    *lock(&LOG_FILE) = Some(file);
     Ok(())
 }
 
-pub fn log(level: &str, message: &str) {
-    let subscribers = SUBSCRIBERS.lock().unwrap();
-    subscribers.iter().for_each(|subscriber| {
-        if level == "NONE" {
-            subscriber(message.to_string());
-        } else {
-            subscriber(format!("{} {}", level.to_uppercase(), message));
+/// 序号大于 since 的历史日志，从旧到新；超出保留条数的部分已丢弃
+pub fn log_history(since: u64) -> WalResult<Vec<LogRecord>> {
+    let history = lock(&HISTORY);
+    Ok(history
+        .iter()
+        .filter(|record| record.seq > since)
+        .cloned()
+        .collect())
+}
+
+pub fn log(level: LogLevel, module: &str, message: String, fields: Vec<(String, String)>) {
+    if level < get_log_level() {
+        return;
+    }
+    let mut record = LogRecord {
+        seq: 0,
+        timestamp: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0),
+        level,
+        module: module.to_string(),
+        message,
+        fields: fields.into_iter().collect(),
+    };
+    // 在历史锁内分配序号，保证历史中的序号有序
+    {
+        let mut history = lock(&HISTORY);
+        record.seq = NEXT_SEQ.fetch_add(1, Ordering::Relaxed);
+        if history.len() == MAX_LOG_HISTORY {
+            history.pop_front();
         }
-    });
+        history.push_back(record.clone());
+    }
+
+    if let Some(file) = lock(&LOG_FILE).as_mut() {
+        file.write_record(&record);
+    }
+    let subscribers = lock(&SUBSCRIBERS);
+    subscribers
+        .iter()
+        .for_each(|subscriber| subscriber(&record));
 }
 
+/// `log!(Info, "message {}", arg)`，或带字段：`log!(Info, { asar = path }; "message")`
 #[macro_export]
 macro_rules! log {
+    ($level:ident, { $($key:ident = $value:expr),* $(,)? }; $($arg:tt)*) => {
+        $crate::utils::log::logger::log(
+            $crate::utils::log::logger::LogLevel::$level,
+            module_path!(),
+            format!($($arg)*),
+            vec![$((stringify!($key).to_string(), $value.to_string())),*],
+        )
+    };
     ($level:ident, $($arg:tt)*) => {
-        crate::utils::log::logger::log(stringify!($level), &format!($($arg)*));
+        $crate::utils::log::logger::log(
+            $crate::utils::log::logger::LogLevel::$level,
+            module_path!(),
+            format!($($arg)*),
+            Vec::new(),
+        )
     };
 }
 
 #[macro_export]
 macro_rules! verbose {
     ($($arg:tt)*) => {
-        log!(VERBOSE, $($arg)*);
+        $crate::log!(Verbose, $($arg)*)
     };
 }
 
 #[macro_export]
 macro_rules! debug {
     ($($arg:tt)*) => {
-        log!(DEBUG, $($arg)*);
+        $crate::log!(Debug, $($arg)*)
     };
 }
 
 #[macro_export]
 macro_rules! info {
     ($($arg:tt)*) => {
-        crate::utils::log::logger::log("NONE", &format!($($arg)*));
+        $crate::log!(Info, $($arg)*)
+    };
+}
+
+#[macro_export]
+macro_rules! warn {
+    ($($arg:tt)*) => {
+        $crate::log!(Warn, $($arg)*)
+    };
+}
+
+#[macro_export]
+macro_rules! error {
+    ($($arg:tt)*) => {
+        $crate::log!(Error, $($arg)*)
     };
 }
@@ -1 +1,3 @@
+pub mod crash;
+pub mod file;
 pub mod logger;
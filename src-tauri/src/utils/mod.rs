@@ -1,5 +1,8 @@
+pub mod doctor;
 pub mod file;
 pub mod lark;
 pub mod log;
 pub mod platform;
+pub mod settings;
+pub mod theme;
 pub mod theme_store;
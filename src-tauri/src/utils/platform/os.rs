@@ -1,4 +1,10 @@
-use std::path::PathBuf;
+use std::{
+    ffi::OsStr,
+    os::windows::ffi::OsStrExt,
+    path::{Component, Path, PathBuf},
+};
+
+use windows_sys::Win32::Storage::FileSystem::GetDiskFreeSpaceExW;
 
 use crate::error::{WalError, WalResult};
 
@@ -11,6 +17,27 @@ pub fn utf8_bytes_to_string(bytes: &[u8]) -> String {
     String::from_utf8_lossy(bytes).to_string()
 }
 
+/// 路径所在磁盘对当前用户可用的剩余空间（字节）
+pub fn free_disk_space(path: &str) -> WalResult<u64> {
+    let wide = OsStr::new(path)
+        .encode_wide()
+        .chain(std::iter::once(0))
+        .collect::<Vec<u16>>();
+    let mut available: u64 = 0;
+    let succeeded = unsafe {
+        GetDiskFreeSpaceExW(
+            wide.as_ptr(),
+            &mut available,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+        )
+    };
+    if succeeded == 0 {
+        return Err(WalError::IoError);
+    }
+    Ok(available)
+}
+
 pub fn join_components(components: &[&str]) -> WalResult<String> {
     components
         .iter()
@@ -19,3 +46,18 @@ pub fn join_components(components: &[&str]) -> WalResult<String> {
         .into_string()
         .map_err(|_| WalError::SystemEncodingError)
 }
+
+/// 用于比较的路径形式：统一分隔符，去掉 `.` 并折叠 `..`，转为小写（Windows 路径不区分大小写）
+pub fn normalize_path(path: &str) -> String {
+    let mut normalized = PathBuf::new();
+    for component in Path::new(path).components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized.to_string_lossy().to_lowercase()
+}
@@ -2,15 +2,16 @@ use std::{ffi::c_void, os::windows::raw::HANDLE};
 
 use tokio::process::Command;
 use windows_sys::Win32::{
-    Foundation::CloseHandle,
+    Foundation::{CloseHandle, GetLastError, ERROR_ACCESS_DENIED, FILETIME, STILL_ACTIVE},
     System::{
         Diagnostics::ToolHelp::{
             CreateToolhelp32Snapshot, Process32FirstW, Process32NextW, PROCESSENTRY32W,
             TH32CS_SNAPPROCESS,
         },
         Threading::{
-            OpenProcess, TerminateProcess, WaitForMultipleObjects, INFINITE, PROCESS_ALL_ACCESS,
-            PROCESS_SYNCHRONIZE,
+            GetExitCodeProcess, GetProcessTimes, OpenProcess, TerminateProcess,
+            WaitForMultipleObjects, INFINITE, PROCESS_ALL_ACCESS,
+            PROCESS_QUERY_LIMITED_INFORMATION, PROCESS_SYNCHRONIZE,
         },
     },
 };
@@ -58,6 +59,50 @@ pub fn is_process_running(process_name: &str) -> bool {
     enum_processes(process_name, |_| false) > 0
 }
 
+/// 按 PID 判断进程是否仍在运行；无权打开的进程视为在运行
+pub fn is_pid_running(pid: u32) -> bool {
+    unsafe {
+        let h_process = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid);
+        if h_process.is_null() {
+            return GetLastError() == ERROR_ACCESS_DENIED;
+        }
+        let mut exit_code: u32 = 0;
+        let queried = GetExitCodeProcess(h_process, &mut exit_code);
+        CloseHandle(h_process);
+        queried != 0 && exit_code == STILL_ACTIVE as u32
+    }
+}
+
+/// 进程的创建时间（FILETIME 的 100ns 计数），与 PID 一起才能唯一标识一个进程；无法查询时返回 None
+pub fn process_start_time(pid: u32) -> Option<u64> {
+    unsafe {
+        let h_process = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid);
+        if h_process.is_null() {
+            return None;
+        }
+        let mut creation = FILETIME::default();
+        let mut exit = FILETIME::default();
+        let mut kernel = FILETIME::default();
+        let mut user = FILETIME::default();
+        let queried = GetProcessTimes(h_process, &mut creation, &mut exit, &mut kernel, &mut user);
+        CloseHandle(h_process);
+        (queried != 0)
+            .then_some((creation.dwHighDateTime as u64) << 32 | creation.dwLowDateTime as u64)
+    }
+}
+
+/// 记录下来的进程是否仍在运行。PID 会被复用，记录了创建时间时还要比对；
+/// 旧记录没有创建时间、或无权查询时只按 PID 判断
+pub fn is_process_alive(pid: u32, started_at: Option<u64>) -> bool {
+    if !is_pid_running(pid) {
+        return false;
+    }
+    match (started_at, process_start_time(pid)) {
+        (Some(recorded), Some(actual)) => recorded == actual,
+        _ => true,
+    }
+}
+
 pub fn kill_all_processes(process_name: &str) -> bool {
     let mut all_killed = true;
     enum_processes(process_name, |pe32| unsafe {
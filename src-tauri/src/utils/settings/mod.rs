@@ -0,0 +1,63 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    error::{WalError, WalResult},
+    shared::{lark_variant_session, set_custom_lark_variants},
+    utils::{
+        lark::variant::LarkVariant,
+        log::logger::{self, LogLevel},
+    },
+    warn,
+};
+
+/// 设置文件：<app_data>/settings.json
+pub fn settings_file(app_data_dir: &Path) -> PathBuf {
+    app_data_dir.join("settings.json")
+}
+
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AppSettings {
+    /// 各客户端手动指定的安装目录，键为 LarkVariant::id；查找时优先于注册表
+    pub lark_path_overrides: BTreeMap<String, String>,
+    /// 内置之外的客户端描述（私有化部署等）
+    pub custom_variants: Vec<LarkVariant>,
+    /// 日志级别，未设置时为 info
+    pub log_level: Option<LogLevel>,
+}
+
+/// 读取设置；不存在时返回默认设置
+pub fn read_settings(app_data_dir: &Path) -> WalResult<AppSettings> {
+    let path = settings_file(app_data_dir);
+    if !path.exists() {
+        return Ok(AppSettings::default());
+    }
+    let content = std::fs::read_to_string(&path).map_err(|_| WalError::IoError)?;
+    serde_json::from_str(&content).map_err(|_| WalError::SerializationError)
+}
+
+pub fn write_settings(app_data_dir: &Path, settings: &AppSettings) -> WalResult<()> {
+    std::fs::create_dir_all(app_data_dir).map_err(|_| WalError::IoError)?;
+    let content =
+        serde_json::to_string_pretty(settings).map_err(|_| WalError::SerializationError)?;
+    std::fs::write(settings_file(app_data_dir), content).map_err(|_| WalError::IoError)
+}
+
+/// 启动时应用设置：日志级别、自定义客户端与手动指定的安装目录
+pub fn apply_settings(settings: AppSettings) {
+    if let Some(level) = settings.log_level {
+        logger::set_log_level(level);
+    }
+    if let Err(e) = set_custom_lark_variants(settings.custom_variants) {
+        warn!("ignoring custom lark variants: {:?}", e);
+    }
+    for (variant, path) in settings.lark_path_overrides {
+        let _ = lark_variant_session(Some(&variant), |helper| {
+            helper.set_path_override(Some(path));
+            Ok(())
+        });
+    }
+}
@@ -0,0 +1,348 @@
+use std::collections::BTreeMap;
+
+use serde_json::{Map, Value};
+
+use crate::utils::theme::{ThemePatch, ThemePatchTarget, WalTheme};
+
+/// 同一选择器下的声明逐键合并，higher 胜出
+fn merge_styles(lower: &Map<String, Value>, higher: &Map<String, Value>) -> Map<String, Value> {
+    let mut out = lower.clone();
+    for (selector, declarations) in higher {
+        let merged = match (out.get(selector), declarations) {
+            (Some(Value::Object(lower)), Value::Object(higher)) => {
+                let mut merged = lower.clone();
+                merged.extend(higher.clone());
+                Value::Object(merged)
+            }
+            _ => declarations.clone(),
+        };
+        out.insert(selector.clone(), merged);
+    }
+    out
+}
+
+/// 低优先级在前、高优先级在后，保证高优先级脚本最后执行、可覆盖前者；
+/// css 同理，后出现的规则胜出
+fn concat_script(lower: Option<&str>, higher: Option<&str>) -> String {
+    [lower, higher]
+        .into_iter()
+        .map(|script| script.unwrap_or("").trim())
+        .filter(|script| !script.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn merge_patch(lower: &ThemePatch, higher: &ThemePatch) -> ThemePatch {
+    let mut color_overrides = lower.color_overrides.clone();
+    color_overrides.extend(higher.color_overrides.clone());
+    let target = match (&lower.target, &higher.target) {
+        (
+            ThemePatchTarget::MainScript {
+                all_entries: lower_all,
+                ..
+            },
+            ThemePatchTarget::MainScript {
+                subject,
+                all_entries: higher_all,
+            },
+        ) => ThemePatchTarget::MainScript {
+            subject: subject.clone(),
+            all_entries: *lower_all || *higher_all,
+        },
+        (
+            ThemePatchTarget::Html {
+                stylesheets: lower_sheets,
+                ..
+            },
+            ThemePatchTarget::Html {
+                subject,
+                stylesheets: higher_sheets,
+            },
+        ) => {
+            let mut stylesheets = lower_sheets.clone();
+            for href in higher_sheets {
+                if !stylesheets.contains(href) {
+                    stylesheets.push(href.clone());
+                }
+            }
+            ThemePatchTarget::Html {
+                subject: subject.clone(),
+                stylesheets,
+            }
+        }
+        _ => higher.target.clone(),
+    };
+    ThemePatch {
+        target,
+        style_overrides_by_selector: merge_styles(
+            &lower.style_overrides_by_selector,
+            &higher.style_overrides_by_selector,
+        ),
+        color_overrides,
+        enable_dev_tools: lower.enable_dev_tools || higher.enable_dev_tools,
+        custom_script: Some(concat_script(
+            lower.custom_script.as_deref(),
+            higher.custom_script.as_deref(),
+        )),
+        custom_css: Some(concat_script(
+            lower.custom_css.as_deref(),
+            higher.custom_css.as_deref(),
+        )),
+        description: higher
+            .description
+            .clone()
+            .filter(|description| !description.is_empty())
+            .or_else(|| lower.description.clone()),
+    }
+}
+
+fn normalize_patch(patch: &ThemePatch) -> ThemePatch {
+    ThemePatch {
+        custom_script: Some(concat_script(None, patch.custom_script.as_deref())),
+        custom_css: Some(concat_script(None, patch.custom_css.as_deref())),
+        ..patch.clone()
+    }
+}
+
+/// 与前端 mergeThemes 相同：themes 按优先级排序，下标 0 最高。
+/// 按 asar 分组、组内按落点分桶，同桶合并样式与颜色、按低到高拼接脚本。
+pub fn merge_themes(themes: &[WalTheme]) -> WalTheme {
+    let mut by_asar: BTreeMap<String, Vec<ThemePatch>> = BTreeMap::new();
+    for theme in themes.iter().rev() {
+        for (asar, patches) in &theme.asar_patches {
+            if patches.is_empty() {
+                continue;
+            }
+            let bucket = by_asar.entry(asar.clone()).or_default();
+            for patch in patches {
+                let key = patch.target.key();
+                match bucket
+                    .iter_mut()
+                    .find(|existing| existing.target.key() == key)
+                {
+                    Some(existing) => *existing = merge_patch(existing, patch),
+                    None => bucket.push(normalize_patch(patch)),
+                }
+            }
+        }
+    }
+    WalTheme {
+        asar_patches: by_asar,
+        ..WalTheme::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::*;
+
+    /// 与前端 merge.test.ts 对应的用例，两端合并规则须保持一致
+    fn theme(asar_patches: Value) -> WalTheme {
+        serde_json::from_value(json!({ "asarPatches": asar_patches })).unwrap()
+    }
+
+    fn file_theme(asar: &str, patch: Value) -> WalTheme {
+        let mut full = json!({
+            "kind": "file",
+            "styleOverridesBySelector": {},
+            "colorOverrides": {},
+            "enableDevTools": false,
+            "customScript": "",
+        });
+        full.as_object_mut()
+            .unwrap()
+            .extend(patch.as_object().unwrap().clone());
+        theme(json!({ asar: [full] }))
+    }
+
+    fn to_json<T: serde::Serialize>(value: &T) -> Value {
+        serde_json::to_value(value).unwrap()
+    }
+
+    #[test]
+    fn returns_an_empty_theme_when_given_no_themes() {
+        assert!(merge_themes(&[]).asar_patches.is_empty());
+    }
+
+    #[test]
+    fn merges_two_patches_on_the_same_file() {
+        let high = file_theme(
+            "a.asar",
+            json!({
+                "path": "index.js",
+                "styleOverridesBySelector": { ".a": { "color": "red" } },
+                "colorOverrides": { "--bg": "black", "--fg": "white" },
+            }),
+        );
+        let low = file_theme(
+            "a.asar",
+            json!({
+                "path": "index.js",
+                "styleOverridesBySelector": { ".b": { "color": "blue" } },
+                "colorOverrides": { "--bg": "green" },
+            }),
+        );
+
+        let merged = merge_themes(&[high, low]);
+        let patches = &merged.asar_patches["a.asar"];
+        assert_eq!(patches.len(), 1);
+        assert_eq!(
+            to_json(&patches[0].style_overrides_by_selector),
+            json!({ ".a": { "color": "red" }, ".b": { "color": "blue" } })
+        );
+        assert_eq!(
+            to_json(&patches[0].color_overrides),
+            json!({ "--bg": "black", "--fg": "white" })
+        );
+    }
+
+    #[test]
+    fn keeps_patches_on_different_files_separate() {
+        let one = file_theme(
+            "a.asar",
+            json!({ "path": "one.js", "colorOverrides": { "--x": "1" } }),
+        );
+        let two = file_theme(
+            "a.asar",
+            json!({ "path": "two.js", "colorOverrides": { "--y": "2" } }),
+        );
+
+        assert_eq!(merge_themes(&[one, two]).asar_patches["a.asar"].len(), 2);
+    }
+
+    #[test]
+    fn keeps_patches_across_different_asars() {
+        let a = file_theme("a.asar", json!({ "path": "index.js" }));
+        let b = file_theme("b.asar", json!({ "path": "index.js" }));
+
+        let merged = merge_themes(&[a, b]);
+        assert_eq!(
+            merged.asar_patches.keys().collect::<Vec<_>>(),
+            ["a.asar", "b.asar"]
+        );
+    }
+
+    #[test]
+    fn concatenates_custom_scripts_low_priority_first() {
+        let high = file_theme(
+            "a.asar",
+            json!({ "path": "index.js", "customScript": "HIGH" }),
+        );
+        let low = file_theme(
+            "a.asar",
+            json!({ "path": "index.js", "customScript": "LOW" }),
+        );
+
+        let merged = merge_themes(&[high, low]);
+        assert_eq!(
+            merged.asar_patches["a.asar"][0].custom_script.as_deref(),
+            Some("LOW\nHIGH")
+        );
+    }
+
+    #[test]
+    fn ors_enable_dev_tools() {
+        let off = file_theme(
+            "a.asar",
+            json!({ "path": "index.js", "enableDevTools": false }),
+        );
+        let on = file_theme(
+            "a.asar",
+            json!({ "path": "index.js", "enableDevTools": true }),
+        );
+
+        assert!(merge_themes(&[off, on]).asar_patches["a.asar"][0].enable_dev_tools);
+    }
+
+    #[test]
+    fn treats_main_script_patches_with_the_same_subject_as_one_target() {
+        let make = |color: &str| {
+            theme(json!({ "a.asar": [{
+                "kind": "main-script",
+                "subject": "main",
+                "colorOverrides": { "--c": color },
+            }] }))
+        };
+
+        let merged = merge_themes(&[make("red"), make("blue")]);
+        let patches = &merged.asar_patches["a.asar"];
+        assert_eq!(patches.len(), 1);
+        assert_eq!(
+            patches[0].target,
+            ThemePatchTarget::MainScript {
+                subject: "main".to_string(),
+                all_entries: false,
+            }
+        );
+        assert_eq!(
+            to_json(&patches[0].color_overrides),
+            json!({ "--c": "red" })
+        );
+    }
+
+    #[test]
+    fn targets_all_entries_when_any_merged_patch_asks_for_it() {
+        let make = |all_entries: bool| {
+            theme(json!({ "a.asar": [{
+                "kind": "main-script",
+                "subject": "main",
+                "allEntries": all_entries,
+            }] }))
+        };
+
+        let merged = merge_themes(&[make(false), make(true)]);
+        assert!(matches!(
+            merged.asar_patches["a.asar"][0].target,
+            ThemePatchTarget::MainScript {
+                all_entries: true,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn unions_stylesheets_and_concatenates_custom_css_for_html_patches() {
+        let make = |href: &str, custom_css: &str| {
+            theme(json!({ "a.asar": [{
+                "kind": "html",
+                "subject": "main",
+                "customCss": custom_css,
+                "stylesheets": ["shared.css", href],
+            }] }))
+        };
+
+        let merged = merge_themes(&[make("high.css", "HIGH"), make("low.css", "LOW")]);
+        let patch = &merged.asar_patches["a.asar"][0];
+        assert_eq!(
+            patch.target,
+            ThemePatchTarget::Html {
+                subject: "main".to_string(),
+                stylesheets: vec![
+                    "shared.css".to_string(),
+                    "low.css".to_string(),
+                    "high.css".to_string(),
+                ],
+            }
+        );
+        assert_eq!(patch.custom_css.as_deref(), Some("LOW\nHIGH"));
+    }
+
+    #[test]
+    fn does_not_mutate_the_input_themes() {
+        let themes = [
+            file_theme(
+                "a.asar",
+                json!({ "path": "index.js", "colorOverrides": { "--x": "1" } }),
+            ),
+            file_theme(
+                "a.asar",
+                json!({ "path": "index.js", "colorOverrides": { "--x": "2" } }),
+            ),
+        ];
+        let snapshot = to_json(&themes);
+        merge_themes(&themes);
+        assert_eq!(to_json(&themes), snapshot);
+    }
+}
@@ -0,0 +1,89 @@
+pub mod merge;
+pub mod script;
+pub mod style;
+
+use std::collections::BTreeMap;
+
+use serde_json::{Map, Value};
+
+use crate::error::{WalError, WalResult};
+
+/// 补丁落点：主脚本（按 subject 查找）、asar 内的指定脚本，
+/// 或直接写入样式表、页面 index.html 的样式
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case", tag = "kind")]
+pub enum ThemePatchTarget {
+    /// all_entries 为 true 时补丁写入页面引用的每个脚本，而不只是入口脚本
+    #[serde(rename_all = "camelCase")]
+    MainScript {
+        subject: String,
+        #[serde(default)]
+        all_entries: bool,
+    },
+    File {
+        path: String,
+    },
+    /// 样式追加到 path 指向的 .css 文件，不经过脚本
+    Css {
+        path: String,
+    },
+    /// 样式以 `<style>` 插入 subject 目录下的 index.html，stylesheets 中的地址以 `<link>` 引入
+    Html {
+        subject: String,
+        #[serde(default)]
+        stylesheets: Vec<String>,
+    },
+}
+
+impl ThemePatchTarget {
+    /// 同一 key 的补丁落在同一个内部文件上，必须合并
+    pub fn key(&self) -> String {
+        match self {
+            Self::MainScript { subject, .. } => format!("main-script::{}", subject),
+            Self::File { path } => format!("file::{}", path),
+            Self::Css { path } => format!("css::{}", path),
+            Self::Html { subject, .. } => format!("html::{}", subject),
+        }
+    }
+}
+
+/// 与前端 WalAsarPatch 相同的结构，字段顺序保持 YAML 中的顺序
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ThemePatch {
+    #[serde(flatten)]
+    pub target: ThemePatchTarget,
+    #[serde(default)]
+    pub style_overrides_by_selector: Map<String, Value>,
+    #[serde(default)]
+    pub color_overrides: Map<String, Value>,
+    #[serde(default)]
+    pub enable_dev_tools: bool,
+    #[serde(default)]
+    pub custom_script: Option<String>,
+    /// 原样写入的 css，仅用于 css 与 html 补丁
+    #[serde(default)]
+    pub custom_css: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WalTheme {
+    #[serde(default)]
+    pub id: Option<String>,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub author: Option<String>,
+    /// 键为 asar 相对路径
+    pub asar_patches: BTreeMap<String, Vec<ThemePatch>>,
+}
+
+/// 解析主题 YAML；与前端 parseTheme 接受的格式一致
+pub fn parse_theme(content: &str) -> WalResult<WalTheme> {
+    serde_yaml::from_str(content).map_err(|_| WalError::SerializationError)
+}
@@ -0,0 +1,71 @@
+use crate::utils::theme::ThemePatch;
+
+/// 生成注入 asar 的脚本，与前端 makeStylesScript 输出一致
+pub fn make_styles_script(patch: &ThemePatch) -> String {
+    let color_overrides =
+        serde_json::to_string(&patch.color_overrides).unwrap_or_else(|_| "{}".to_string());
+    let style_overrides = serde_json::to_string(&patch.style_overrides_by_selector)
+        .unwrap_or_else(|_| "{}".to_string());
+    let custom_script = patch.custom_script.as_deref().unwrap_or("");
+
+    format!(
+        r#"function start() {{
+  const enableDevTools = {enable_dev_tools}
+  const colorOverrides = {color_overrides}
+  Object.entries(colorOverrides).forEach(([key, value]) => {{
+    document.documentElement.style.setProperty(key, value)
+  }})
+
+  const styleOverridesBySelector = {style_overrides}
+
+  const defaultSheet = window.document.styleSheets[0]
+  if (defaultSheet) {{
+    Object.entries(styleOverridesBySelector).forEach(([selector, style]) => {{
+      const rule = `${{selector}} {{ ${{Object.entries(style)
+        .map(([key, value]) => `${{key}}: ${{value}};`)
+        .join(' ')}} }}`
+      defaultSheet.insertRule(rule)
+    }})
+  }}
+
+  if (enableDevTools) {{
+    const el = document.createElement('script')
+    el.src = 'https://cdn.jsdelivr.net/npm/eruda'
+    el.onerror = (e) => {{
+      alert('failed to load eruda: ' + JSON.stringify(e))
+    }}
+    const el2 = document.createElement('script')
+    el2.innerHTML = 'eruda.init(); eruda.show()'
+    document.body.appendChild(el)
+    setTimeout(() => {{
+      document.body.appendChild(el2)
+    }}, 1000)
+  }}
+
+  // WAL CUSTOM SCRIPT START
+  {custom_script}
+  // WAL CUSTOM SCRIPT END
+}}
+
+start()"#,
+        enable_dev_tools = patch.enable_dev_tools,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::theme::style::tests::style_fixtures;
+
+    #[test]
+    fn make_styles_script_matches_frontend() {
+        for fixture in style_fixtures() {
+            assert_eq!(
+                make_styles_script(&fixture.patch),
+                fixture.styles_script,
+                "{}",
+                fixture.name
+            );
+        }
+    }
+}
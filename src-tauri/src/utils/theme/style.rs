@@ -0,0 +1,154 @@
+use serde_json::{Map, Value};
+
+use crate::utils::theme::ThemePatch;
+
+/// 生成直接写入样式表的 css，与前端 makeStylesheet 输出一致：
+/// 颜色覆盖写成 `:root` 上的变量，其后是各选择器的规则与 customCss
+pub fn make_stylesheet(patch: &ThemePatch) -> String {
+    let mut blocks = Vec::new();
+    if !patch.color_overrides.is_empty() {
+        blocks.push(make_rule(":root", &patch.color_overrides));
+    }
+    for (selector, style) in &patch.style_overrides_by_selector {
+        if let Value::Object(style) = style {
+            blocks.push(make_rule(selector, style));
+        }
+    }
+    let custom_css = patch.custom_css.as_deref().unwrap_or("").trim();
+    if !custom_css.is_empty() {
+        blocks.push(custom_css.to_string());
+    }
+    blocks.join("\n")
+}
+
+/// 生成插入 index.html 的标签，与前端 makeHtmlInjection 输出一致：
+/// 先是 stylesheets 的 `<link>`，再是内联的 `<style>`，使主题自己的规则排在后面
+pub fn make_html_injection(patch: &ThemePatch, stylesheets: &[String]) -> String {
+    let mut tags = stylesheets
+        .iter()
+        .map(|href| {
+            format!(
+                r#"<link rel="stylesheet" href="{}">"#,
+                escape_attribute(href)
+            )
+        })
+        .collect::<Vec<_>>();
+    let css = make_stylesheet(patch);
+    if !css.is_empty() {
+        // 避免 css 中的 </style 提前结束标签
+        tags.push(format!(
+            "<style>\n{}\n</style>",
+            css.replace("</style", "<\\/style")
+        ));
+    }
+    tags.join("\n")
+}
+
+fn make_rule(selector: &str, declarations: &Map<String, Value>) -> String {
+    let body = declarations
+        .iter()
+        .map(|(key, value)| format!("  {}: {};\n", key, js_string(value)))
+        .collect::<String>();
+    format!("{} {{\n{}}}", selector, body)
+}
+
+/// 按 JS 模板字符串的规则把值转成文本，YAML 里写成数字、布尔等的值才与前端一致：
+/// 数组以 `,` 连接且 null 为空，对象为 `[object Object]`
+fn js_string(value: &Value) -> String {
+    match value {
+        Value::String(value) => value.clone(),
+        Value::Number(number) => match number.as_f64() {
+            Some(float) if !number.is_i64() && !number.is_u64() => float.to_string(),
+            _ => number.to_string(),
+        },
+        Value::Array(items) => items
+            .iter()
+            .map(|item| match item {
+                Value::Null => String::new(),
+                item => js_string(item),
+            })
+            .collect::<Vec<_>>()
+            .join(","),
+        Value::Object(_) => "[object Object]".to_string(),
+        value => value.to_string(),
+    }
+}
+
+fn escape_attribute(value: &str) -> String {
+    value.replace('&', "&amp;").replace('"', "&quot;")
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::utils::theme::ThemePatchTarget;
+
+    /// 与前端 style-scripts.test.ts 共用的夹具，由前端实现生成
+    #[derive(serde::Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub(crate) struct StyleFixture {
+        pub name: String,
+        pub patch: ThemePatch,
+        pub styles_script: String,
+        pub stylesheet: String,
+        pub html_injection: String,
+    }
+
+    pub(crate) fn style_fixtures() -> Vec<StyleFixture> {
+        serde_json::from_str(include_str!(
+            "../../../../src/helper/__fixtures__/style-scripts.json"
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn make_stylesheet_matches_frontend() {
+        for fixture in style_fixtures() {
+            assert_eq!(
+                make_stylesheet(&fixture.patch),
+                fixture.stylesheet,
+                "{}",
+                fixture.name
+            );
+        }
+    }
+
+    #[test]
+    fn make_html_injection_matches_frontend() {
+        for fixture in style_fixtures() {
+            let stylesheets = match &fixture.patch.target {
+                ThemePatchTarget::Html { stylesheets, .. } => stylesheets.clone(),
+                _ => Vec::new(),
+            };
+            assert_eq!(
+                make_html_injection(&fixture.patch, &stylesheets),
+                fixture.html_injection,
+                "{}",
+                fixture.name
+            );
+        }
+    }
+
+    #[test]
+    fn js_string_follows_template_literals() {
+        let values = serde_json::json!([1.0, 0.25, -3, false, null, ["a", null, [1, 2]], {}]);
+        let texts = values
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(js_string)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            texts,
+            [
+                "1",
+                "0.25",
+                "-3",
+                "false",
+                "null",
+                "a,,1,2",
+                "[object Object]"
+            ]
+        );
+    }
+}
@@ -0,0 +1,22 @@
+import { describe, expect, it } from 'vitest'
+import { makeHtmlInjection, makeStylesheet, makeStylesScript } from './style-scripts'
+import { WalAsarPatch } from '../theme/types'
+import fixtures from './__fixtures__/style-scripts.json'
+
+// 夹具同时被 Rust 端 theme/script.rs、theme/style.rs 的测试读取，两端输出须逐字一致
+describe.each(fixtures)('$name', (fixture) => {
+  const patch = fixture.patch as unknown as WalAsarPatch
+  const stylesheets = patch.kind === 'html' ? (patch.stylesheets ?? []) : []
+
+  it('makeStylesScript matches the shared fixture', () => {
+    expect(makeStylesScript(patch)).toBe(fixture.stylesScript)
+  })
+
+  it('makeStylesheet matches the shared fixture', () => {
+    expect(makeStylesheet(patch)).toBe(fixture.stylesheet)
+  })
+
+  it('makeHtmlInjection matches the shared fixture', () => {
+    expect(makeHtmlInjection(patch, stylesheets)).toBe(fixture.htmlInjection)
+  })
+})
@@ -43,3 +43,43 @@ export function makeStylesScript(patch: WalAsarPatchBase) {
 
 start()`
 }
+
+function makeRule(selector: string, declarations: Record<string, string>): string {
+  const body = Object.entries(declarations)
+    .map(([key, value]) => `  ${key}: ${value};\n`)
+    .join('')
+  return `${selector} {\n${body}}`
+}
+
+/** 直接写入样式表的 css：颜色覆盖写成 :root 上的变量，其后是各选择器的规则与 customCss。与 Rust 端 make_stylesheet 一致 */
+export function makeStylesheet(patch: WalAsarPatchBase): string {
+  const { styleOverridesBySelector = {}, colorOverrides = {}, customCss = '' } = patch
+  const blocks: string[] = []
+  if (Object.keys(colorOverrides).length > 0) {
+    blocks.push(makeRule(':root', colorOverrides))
+  }
+  for (const [selector, style] of Object.entries(styleOverridesBySelector)) {
+    if (style && typeof style === 'object') {
+      blocks.push(makeRule(selector, style))
+    }
+  }
+  if (customCss.trim()) {
+    blocks.push(customCss.trim())
+  }
+  return blocks.join('\n')
+}
+
+function escapeAttribute(value: string): string {
+  return value.replace(/&/g, '&amp;').replace(/"/g, '&quot;')
+}
+
+/** 插入 index.html 的标签：先是 stylesheets 的 <link>，再是内联的 <style>。与 Rust 端 make_html_injection 一致 */
+export function makeHtmlInjection(patch: WalAsarPatchBase, stylesheets: string[] = []): string {
+  const tags = stylesheets.map((href) => `<link rel="stylesheet" href="${escapeAttribute(href)}">`)
+  const css = makeStylesheet(patch)
+  if (css) {
+    // 避免 css 中的 </style 提前结束标签
+    tags.push(`<style>\n${css.replace(/<\/style/g, '<\\/style')}\n</style>`)
+  }
+  return tags.join('\n')
+}
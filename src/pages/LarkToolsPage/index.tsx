@@ -37,6 +37,20 @@ export default function LarkToolsPage() {
       doubleCheck: false,
       action: nativeBridge.launchLark,
     },
+    {
+      title: '诊断',
+      hint: '检查安装、补丁、备份与主题库，结果输出到日志',
+      button: '诊断',
+      doubleCheck: false,
+      action: runDoctor,
+    },
+    {
+      title: '导出诊断包',
+      hint: '把诊断结果、日志、主题与备份信息打包成 zip，主题中的脚本会被隐去',
+      button: '导出',
+      doubleCheck: false,
+      action: exportDiagnosticBundle,
+    },
     {
       title: '打开飞书安装目录',
       hint: '根据注册表记载的位置打开飞书安装目录',
@@ -53,9 +67,43 @@ export default function LarkToolsPage() {
       useLogsStore.getState().add('飞书正在运行，请先关闭')
       return
     }
-    return nativeBridge.withLarkSession((s) => {
-      return s.restoreAllBackups()
-    })
+    try {
+      await nativeBridge.withLarkSession((s) => {
+        return s.restoreAllBackups()
+      })
+    } catch (error) {
+      useLogsStore.getState().add(`恢复备份失败: ${String(error)}`)
+    }
+  }
+
+  async function runDoctor() {
+    const logs = useLogsStore.getState()
+    try {
+      const report = await nativeBridge.runLarkDoctor()
+      const marks = { pass: '✓', warn: '!', fail: '✗' }
+      logs.add(`=== 诊断结果: ${report.status} ===`)
+      report.checks.forEach((check) => logs.add(`${marks[check.status]} ${check.id}: ${check.detail}`))
+    } catch (error) {
+      logs.add(`诊断失败: ${String(error)}`)
+    }
+  }
+
+  async function exportDiagnosticBundle() {
+    const logs = useLogsStore.getState()
+    try {
+      const outputPath = await nativeBridge.pickDiagnosticBundlePath()
+      if (!outputPath) {
+        return
+      }
+      await nativeBridge.exportDiagnosticBundle({
+        outputPath,
+        logs: logs.logs.map((log) => `${new Date(log.timestamp).toISOString()} ${log.text}`),
+        redactScripts: true,
+      })
+      logs.add(`诊断包已导出: ${outputPath}`)
+    } catch (error) {
+      logs.add(`导出诊断包失败: ${String(error)}`)
+    }
   }
 
   function handleExecuteTool(tool: Tool) {
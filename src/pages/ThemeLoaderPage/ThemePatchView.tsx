@@ -1,7 +1,14 @@
 import React from 'react'
-import { WalTheme } from '../../theme/types'
+import { WalAsarPatch, WalTheme } from '../../theme/types'
 import { isColorLike } from './utils'
 
+const PATCH_KIND_LABELS: Record<WalAsarPatch['kind'], string> = {
+  'main-script': '主脚本',
+  file: '文件',
+  css: '样式表',
+  html: '页面',
+}
+
 /** 只读地展示一个主题包含的所有 asar 补丁（样式 / 颜色 / 自定义脚本）。 */
 export function ThemePatchView({ theme }: { theme: WalTheme }) {
   const asarFiles = Object.keys(theme.asarPatches)
@@ -22,7 +29,7 @@ export function ThemePatchView({ theme }: { theme: WalTheme }) {
               return (
                 <div key={index} className="theme-loader-patch-content">
                   <span className="font-medium">
-                    补丁 #{index + 1} · {patch.kind === 'main-script' ? '主脚本' : '文件'}
+                    补丁 #{index + 1} · {PATCH_KIND_LABELS[patch.kind]}
                     {patch.description ? ` · ${patch.description}` : ''}
                   </span>
 
@@ -61,6 +68,13 @@ export function ThemePatchView({ theme }: { theme: WalTheme }) {
                       <div className="theme-loader-code-block">{patch.customScript}</div>
                     </React.Fragment>
                   ) : null}
+
+                  {patch.customCss ? (
+                    <React.Fragment>
+                      <div className="font-medium">自定义样式</div>
+                      <div className="theme-loader-code-block">{patch.customCss}</div>
+                    </React.Fragment>
+                  ) : null}
                 </div>
               )
             })}
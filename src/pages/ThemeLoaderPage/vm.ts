@@ -1,12 +1,51 @@
-import { useEffect, useRef } from 'react'
+import { useEffect } from 'react'
 import { getCurrentWindow } from '@tauri-apps/api/window'
-import { makeStylesScript } from '../../helper/style-scripts'
-import { LarkSession, nativeBridge } from '../../ports/bridge'
+import { makeHtmlInjection, makeStylesheet, makeStylesScript } from '../../helper/style-scripts'
+import { LarkSession, nativeBridge, PatchPlan, PreflightProblem } from '../../ports/bridge'
 import { useLogsStore } from '../../store/logs'
 import { useWindowTitle } from '../../utils/use-title'
 import { useThemeEngineStore } from '../../store/theme-engine'
 import { useThemeLibraryStore } from '../../store/theme-library'
 import { mergeThemes } from '../../theme/merge'
+import { WalAsarPatch } from '../../theme/types'
+
+function describePreflightProblem(problem: PreflightProblem): string {
+  switch (problem.kind) {
+    case 'larkRunning':
+      return '飞书正在运行，请先关闭'
+    case 'asarMissing':
+      return `找不到 ${problem.asarPath}`
+    case 'notWritable':
+      return `${problem.asarPath} 不可写，请检查权限`
+    case 'fileInUse':
+      return `${problem.asarPath} 正被其他程序占用`
+    case 'insufficientSpace':
+      return `磁盘空间不足，需要 ${Math.ceil(problem.required / 1024 / 1024)} MB，剩余 ${Math.floor(problem.available / 1024 / 1024)} MB`
+  }
+}
+
+// 按补丁类型提交：脚本类注入样式脚本，css 与 html 直接写入样式，加载时不会闪烁
+function submitThemePatch(session: LarkSession, asarPath: string, patch: WalAsarPatch): Promise<PatchPlan> {
+  switch (patch.kind) {
+    case 'main-script':
+      return session.submitMainScriptPatch({
+        asarPath,
+        subject: patch.subject,
+        script: makeStylesScript(patch),
+        allEntries: patch.allEntries,
+      })
+    case 'file':
+      return session.submitPatch({ asarPath, innerPath: patch.path, script: makeStylesScript(patch) })
+    case 'css':
+      return session.submitStylesheetPatch({ asarPath, innerPath: patch.path, css: makeStylesheet(patch) })
+    case 'html':
+      return session.submitHtmlPatch({
+        asarPath,
+        subject: patch.subject,
+        html: makeHtmlInjection(patch, patch.stylesheets),
+      })
+  }
+}
 
 export function useThemeLoaderViewModel() {
   const logsStore = useLogsStore()
@@ -15,19 +54,8 @@ export function useThemeLoaderViewModel() {
 
   const windowTitle = useWindowTitle()
 
-  const sessionRef = useRef<LarkSession | null>(null)
-
   const enabledCount = library.items.filter((item) => item.entry.enabled).length
 
-  async function ensureSession(): Promise<LarkSession> {
-    if (sessionRef.current) {
-      return sessionRef.current
-    }
-    const session = await nativeBridge.createLarkSession()
-    sessionRef.current = session
-    return session
-  }
-
   async function handleApply() {
     if (await nativeBridge.isLarkRunning()) {
       logsStore.add('飞书正在运行，请先关闭')
@@ -45,38 +73,53 @@ export function useThemeLoaderViewModel() {
       themeEngineStore.setCurrentProgress(0)
       themeEngineStore.setMaxProgress(entries.length + 1)
 
-      const session = await ensureSession()
-      logsStore.add('正在还原到官方原版...')
-      try {
-        await session.restoreAllBackups()
-      } catch (error) {
-        logsStore.add(`还原失败: ${String(error)}`)
-      }
-      themeEngineStore.incrementCurrentProgress()
-
-      for (const [asarFile, patches] of entries) {
-        logsStore.add(`正在修改 asar 文件: ${asarFile}`)
-
-        for (let index = 0; index < patches.length; index++) {
-          logsStore.add(`补丁 #${index + 1} 开始应用...`)
-          const patch = patches[index]
-          const script = makeStylesScript(patch)
+      // 每次应用使用独立的会话，结束后立即关闭，避免原生端一直持有整份 asar
+      await nativeBridge.withLarkSession(async (session) => {
+        logsStore.add('正在还原到官方原版...')
+        try {
+          await session.restoreAllBackups()
+        } catch (error) {
+          logsStore.add(`还原失败: ${String(error)}`)
+        }
+        themeEngineStore.incrementCurrentProgress()
 
-          if (patch.kind === 'main-script') {
-            await session.submitMainScriptPatch({ asarPath: asarFile, subject: patch.subject, script })
-          } else {
-            await session.submitPatch({ asarPath: asarFile, innerPath: patch.path, script })
+        for (const [asarFile, patches] of entries) {
+          logsStore.add(`正在准备 asar 文件: ${asarFile}`)
+
+          for (let index = 0; index < patches.length; index++) {
+            logsStore.add(`补丁 #${index + 1} 开始提交...`)
+            const patch = patches[index]
+            const plan = await submitThemePatch(session, asarFile, patch)
+            if (plan.targets.length > 1) {
+              logsStore.add(`补丁 #${index + 1} 匹配到 ${plan.targets.length} 个文件`)
+            }
+            plan.skipped.forEach((skipped) => logsStore.add(`已跳过 ${skipped.path}: ${skipped.reason}`))
           }
         }
 
+        const problems = await session.preflight()
+        if (problems.length > 0) {
+          problems.forEach((problem) => logsStore.add(`无法写入: ${describePreflightProblem(problem)}`))
+          throw new Error('写入前检查未通过')
+        }
+
         logsStore.add('正在写入文件...')
         try {
-          await session.applyPatches()
+          const summary = await session.applyPatchesWithProgress((event) => {
+            if (event.event === 'progress' && event.data.phase === 'write') {
+              logsStore.add(`正在写入 ${event.data.asarPath}...`)
+            }
+            if (event.event === 'progress' && event.data.phase === 'done') {
+              themeEngineStore.incrementCurrentProgress()
+            }
+          })
+          if (summary.cancelled) {
+            logsStore.add('写入已取消')
+          }
         } catch (error) {
           logsStore.add(`写入文件失败: ${String(error)}`)
         }
-        themeEngineStore.incrementCurrentProgress()
-      }
+      })
       themeEngineStore.setCurrentProgress(themeEngineStore.maxProgress)
       themeEngineStore.setWorkingState('done')
     } catch (error) {
@@ -94,8 +137,7 @@ export function useThemeLoaderViewModel() {
       return
     }
     try {
-      const session = await ensureSession()
-      await session.restoreAllBackups()
+      await nativeBridge.withLarkSession((session) => session.restoreAllBackups())
       logsStore.add('所有备份已恢复')
     } catch (error) {
       logsStore.add(`恢复备份失败: ${String(error)}`)
@@ -141,11 +183,34 @@ export function useThemeLoaderViewModel() {
     nativeBridge.getLarkBasePath().then((path) => {
       logsStore.add(`找到飞书路径: ${path}`)
     })
-    const unsubscribe = nativeBridge.subscribeToLogEvents((message) => {
-      logsStore.add(message)
+    nativeBridge.getRecoveredApplies().then((applies) => {
+      for (const apply of applies) {
+        const action = apply.outcome === 'completed' ? '已补完' : '已回退'
+        logsStore.add(`检测到上次应用被中断，${action}: ${apply.targets.join(', ')}`)
+        apply.problems.forEach((problem) => logsStore.add(`恢复时出错: ${problem}`))
+      }
+    })
+    nativeBridge.getPreviousCrash().then((report) => {
+      if (report) {
+        logsStore.add(`上次运行时原生端崩溃: ${report.message} (${report.location ?? 'unknown'})`)
+      }
+    })
+    const lastNativeSeq = useLogsStore.getState().lastNativeSeq
+    const unsubscribe = nativeBridge.subscribeToLogEvents(lastNativeSeq, (record) => {
+      logsStore.addNative(record)
+    })
+    const unsubscribePanics = nativeBridge.subscribeToNativePanics((report) => {
+      logsStore.add(`原生端崩溃: ${report.message} (${report.location ?? 'unknown'})`)
+    })
+    const unsubscribePathChanges = nativeBridge.subscribeToLarkPathChanges((change) => {
+      if (change.previous) {
+        logsStore.add(`飞书路径已变更: ${change.current.path}`)
+      }
     })
     return () => {
       unsubscribe.then((unsubscribe) => unsubscribe())
+      unsubscribePanics.then((unsubscribe) => unsubscribe())
+      unsubscribePathChanges.then((unsubscribe) => unsubscribe())
     }
     // eslint-disable-next-line react-hooks/exhaustive-deps
   }, [])
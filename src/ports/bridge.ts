@@ -1,4 +1,4 @@
-import { invoke } from '@tauri-apps/api/core'
+import { Channel, invoke } from '@tauri-apps/api/core'
 import { listen } from '@tauri-apps/api/event'
 import { getVersion } from '@tauri-apps/api/app'
 
@@ -8,12 +8,67 @@ export interface SubmitPatchPayload {
   asarPath: string
   innerPath: string
   script: string
+  /** 提交者，通常是主题 id，便于之后查看或撤回 */
+  owner?: string
+}
+
+export interface PatchPlan {
+  /** 会写入补丁的内部文件 */
+  targets: string[]
+  /** glob 匹配到、但不适合打补丁的路径 */
+  skipped: { path: string; reason: string }[]
 }
 
 export interface SubmitMainScriptPatchPayload {
   asarPath: string
   subject: string
   script: string
+  owner?: string
+  /** 给 index.html 引用的每个脚本都打补丁，而不只是入口脚本 */
+  allEntries?: boolean
+}
+
+export interface SubmitStylesheetPatchPayload {
+  asarPath: string
+  innerPath: string
+  css: string
+  owner?: string
+}
+
+export interface SubmitHtmlPatchPayload {
+  asarPath: string
+  subject: string
+  /** 插入 </head> 之前的标签 */
+  html: string
+  owner?: string
+}
+
+export interface QueuedPatch {
+  innerPath: string
+  size: number
+  owner: string | null
+  /** 注入代码块的开头部分 */
+  preview: string
+}
+
+export interface QueuedAsarPatches {
+  asarPath: string
+  patches: QueuedPatch[]
+}
+
+export interface ExportedPatch {
+  innerPath: string
+  size: number
+  sha256: string
+}
+
+/** 与导出的 asar 一同写出的 <output>.json 说明文件。 */
+export interface ExportManifest {
+  sourceAsar: string
+  sourceSha256: string
+  outputSha256: string
+  exportedAt: number
+  patches: ExportedPatch[]
 }
 
 export interface PickedTheme {
@@ -22,6 +77,180 @@ export interface PickedTheme {
   content: string
 }
 
+/** 客户端标识：内置 'feishu' / 'lark'，或自定义的私有化部署 id。缺省为飞书。 */
+export type LarkVariantId = string
+
+export interface LarkVariant {
+  id: LarkVariantId
+  displayName: string
+  executable: string
+  registryKey: string | null
+  installRoots: string[]
+  iniFile: string
+  /** 版本目录下必须存在的 asar；为空时只要求至少有一个 asar */
+  expectedAsars?: string[]
+}
+
+export interface DetectedLarkVariant {
+  variant: LarkVariant
+  location: LarkLocation | null
+  running: boolean
+}
+
+export type LarkLocationSource = 'override' | 'registry' | 'wellKnown'
+
+export interface LarkLocation {
+  installDir: string
+  version: string
+  path: string
+  source: LarkLocationSource
+}
+
+export interface LarkInstallValidation {
+  installDir: string | null
+  version: string | null
+  versionDirExists: boolean
+  asarFiles: string[]
+  /** 客户端需要、但版本目录下没有的 asar */
+  missingAsars: string[]
+  valid: boolean
+}
+
+export interface LarkPathChange {
+  variant: LarkVariantId
+  previous: LarkLocation | null
+  current: LarkLocation
+}
+
+export type PreflightProblem =
+  | { kind: 'larkRunning'; variant: LarkVariantId }
+  | { kind: 'asarMissing'; asarPath: string }
+  | { kind: 'notWritable'; asarPath: string }
+  | { kind: 'fileInUse'; asarPath: string }
+  | { kind: 'insufficientSpace'; required: number; available: number }
+
+export type DoctorCheckStatus = 'pass' | 'warn' | 'fail'
+
+export interface DoctorCheck {
+  // 检查项标识，如 install、asar:resources/app.asar
+  id: string
+  status: DoctorCheckStatus
+  detail: string
+}
+
+export interface DoctorReport {
+  variant: LarkVariantId
+  generatedAt: number
+  // 所有检查项中最差的结果
+  status: DoctorCheckStatus
+  location: LarkLocation | null
+  checks: DoctorCheck[]
+}
+
+export interface AsarTreeNode {
+  name: string
+  // 以 / 分隔的内部路径，根节点为空串
+  path: string
+  kind: 'directory' | 'file' | 'link'
+  // 目录为其下所有文件之和
+  size: number
+  unpacked: boolean
+  executable: boolean
+  link: string | null
+  children: AsarTreeNode[]
+}
+
+export interface AsarSearchQuery {
+  pattern: string
+  // 为 false 时按子串匹配
+  regex?: boolean
+  caseSensitive?: boolean
+  pathPrefix?: string
+  maxResults?: number
+}
+
+export interface AsarSearchMatch {
+  path: string
+  line: number
+  column: number
+  preview: string
+}
+
+export interface RecoveredApply {
+  sessionId: string
+  startedAt: number
+  // completed: 中断时已在替换，已补完；rolledBack: 中断时还在写临时文件，已清理
+  outcome: 'completed' | 'rolledBack'
+  targets: string[]
+  problems: string[]
+}
+
+export interface CrashReport {
+  timestamp: number
+  thread: string
+  message: string
+  // panic 发生的源码位置，file:line:column
+  location: string | null
+  backtrace: string
+}
+
+export type ApplyPhase = 'backup' | 'rewrite' | 'write' | 'done'
+
+export interface ApplyProgress {
+  asarPath: string
+  asarIndex: number
+  totalAsars: number
+  phase: ApplyPhase
+  // 从源 asar 拷贝的字节数，补丁替换的内容不计入
+  bytesRead: number
+  bytesWritten: number
+}
+
+export interface ApplySummary {
+  applied: string[]
+  cancelled: boolean
+  bytesRead: number
+  bytesWritten: number
+  elapsedMs: number
+}
+
+export type ApplyEvent =
+  | { event: 'started'; data: { totalAsars: number } }
+  | { event: 'progress'; data: ApplyProgress }
+  | { event: 'finished'; data: ApplySummary }
+  | { event: 'failed'; data: { message: string } }
+
+export type LogLevel = 'verbose' | 'debug' | 'info' | 'warn' | 'error'
+
+export interface LogRecord {
+  /** 进程内递增的序号 */
+  seq: number
+  timestamp: number
+  level: LogLevel
+  module: string
+  message: string
+  fields: Record<string, string>
+}
+
+export type LarkSessionTarget = { kind: 'installed'; value: LarkVariantId } | { kind: 'directory'; value: string }
+
+export interface LarkSessionAsarInfo {
+  asarPath: string
+  patchCount: number
+  asarBytes: number
+}
+
+export interface LarkSessionInfo {
+  id: LarkSessionId
+  target: LarkSessionTarget
+  createdAt: number
+  idleSeconds: number
+  /** 会话正在执行命令，此时不统计其内容 */
+  busy: boolean
+  memoryBytes: number
+  asars: LarkSessionAsarInfo[]
+}
+
 export class LarkSession {
   private readonly id: LarkSessionId
   private readonly nativeBridge: NativeBridge
@@ -31,26 +260,96 @@ export class LarkSession {
     this.nativeBridge = nativeBridge
   }
 
-  submitPatch(payload: SubmitPatchPayload): Promise<void> {
-    return this.nativeBridge.invokeLarkSession(this.id, 'submit_patch', [
+  // innerPath 可以是 glob（如 webcontent/*/index.*.js）；dryRun 为真时只展开不排队
+  async submitPatch(payload: SubmitPatchPayload, dryRun = false): Promise<PatchPlan> {
+    const command = dryRun ? 'dry_run_patch' : 'submit_patch'
+    const raw = await this.nativeBridge.invokeLarkSession<string>(this.id, command, [
       payload.asarPath,
       payload.innerPath,
       payload.script,
+      ...(payload.owner ? [payload.owner] : []),
     ])
+    return JSON.parse(raw) as PatchPlan
   }
 
-  submitMainScriptPatch(payload: SubmitMainScriptPatchPayload): Promise<void> {
-    return this.nativeBridge.invokeLarkSession(this.id, 'submit_main_script_patch', [
+  // subject 可以是 glob，匹配含 index.html 的目录
+  async submitMainScriptPatch(payload: SubmitMainScriptPatchPayload, dryRun = false): Promise<PatchPlan> {
+    const command = dryRun ? 'dry_run_main_script_patch' : 'submit_main_script_patch'
+    const raw = await this.nativeBridge.invokeLarkSession<string>(this.id, command, [
       payload.asarPath,
       payload.subject,
       payload.script,
+      ...(payload.owner || payload.allEntries ? [payload.owner ?? ''] : []),
+      ...(payload.allEntries ? ['true'] : []),
+    ])
+    return JSON.parse(raw) as PatchPlan
+  }
+
+  // css 追加到样式表末尾；innerPath 可以是 glob，只展开到 .css 文件
+  async submitStylesheetPatch(payload: SubmitStylesheetPatchPayload, dryRun = false): Promise<PatchPlan> {
+    const command = dryRun ? 'dry_run_stylesheet_patch' : 'submit_stylesheet_patch'
+    const raw = await this.nativeBridge.invokeLarkSession<string>(this.id, command, [
+      payload.asarPath,
+      payload.innerPath,
+      payload.css,
+      ...(payload.owner ? [payload.owner] : []),
+    ])
+    return JSON.parse(raw) as PatchPlan
+  }
+
+  // html 插入 subject 目录下 index.html 的 </head> 之前；subject 可以是 glob
+  async submitHtmlPatch(payload: SubmitHtmlPatchPayload, dryRun = false): Promise<PatchPlan> {
+    const command = dryRun ? 'dry_run_html_patch' : 'submit_html_patch'
+    const raw = await this.nativeBridge.invokeLarkSession<string>(this.id, command, [
+      payload.asarPath,
+      payload.subject,
+      payload.html,
+      ...(payload.owner ? [payload.owner] : []),
     ])
+    return JSON.parse(raw) as PatchPlan
+  }
+
+  async listQueuedPatches(): Promise<QueuedAsarPatches[]> {
+    const raw = await this.nativeBridge.invokeLarkSession<string>(this.id, 'list_queued_patches', [])
+    return JSON.parse(raw) as QueuedAsarPatches[]
+  }
+
+  // 撤回某个 asar 中一个内部文件的补丁
+  removeQueuedPatch(asarPath: string, innerPath: string): Promise<void> {
+    return this.nativeBridge.invokeLarkSession(this.id, 'remove_queued_patch', [asarPath, innerPath])
+  }
+
+  clearQueuedPatches(asarPath: string): Promise<void> {
+    return this.nativeBridge.invokeLarkSession(this.id, 'clear_queued_patches', [asarPath])
   }
 
   applyPatches(): Promise<void> {
     return this.nativeBridge.invokeLarkSession(this.id, 'apply_patches', [])
   }
 
+  // 写入前检查，返回所有阻塞问题；为空时可以应用
+  async preflight(): Promise<PreflightProblem[]> {
+    const raw = await this.nativeBridge.invokeLarkSession<string>(this.id, 'preflight', [])
+    return JSON.parse(raw) as PreflightProblem[]
+  }
+
+  // 在原生后台任务中应用，逐阶段推送进度；可用 cancelApply 取消
+  applyPatchesWithProgress(onEvent: (event: ApplyEvent) => void): Promise<ApplySummary> {
+    const channel = new Channel<ApplyEvent>()
+    channel.onmessage = onEvent
+    return invoke<ApplySummary>('apply_lark_session', { id: this.id, onProgress: channel })
+  }
+
+  cancelApply(): Promise<boolean> {
+    return invoke<boolean>('cancel_lark_apply', { id: this.id })
+  }
+
+  // 把某个 asar 已提交的补丁写到指定位置（不修改安装目录），同时写出 <output>.json 说明文件
+  async exportPatches(asarPath: string, outputPath: string): Promise<ExportManifest> {
+    const raw = await this.nativeBridge.invokeLarkSession<string>(this.id, 'export_patches', [asarPath, outputPath])
+    return JSON.parse(raw) as ExportManifest
+  }
+
   async backupExists(path: string): Promise<boolean> {
     return (await this.nativeBridge.invokeLarkSession(this.id, 'backup_exists', [path])) === 'true'
   }
@@ -78,8 +377,13 @@ export class LarkSession {
 }
 
 export class NativeBridge {
-  createLarkSession(): Promise<LarkSession> {
-    return invoke<string>('create_lark_session').then((id) => new LarkSession(id, this))
+  createLarkSession(variant?: LarkVariantId): Promise<LarkSession> {
+    return invoke<string>('create_lark_session', { variant }).then((id) => new LarkSession(id, this))
+  }
+
+  // 绑定到指定目录的会话：不碰真实安装，用于离线准备补丁或测试拷贝出来的飞书目录
+  createLarkDirectorySession(basePath: string): Promise<LarkSession> {
+    return invoke<string>('create_lark_directory_session', { basePath }).then((id) => new LarkSession(id, this))
   }
 
   closeLarkSession(id: LarkSessionId): Promise<void> {
@@ -90,36 +394,115 @@ export class NativeBridge {
     return invoke<T>('invoke_lark_session', { id, command, args })
   }
 
-  async withLarkSession<T>(callback: (session: LarkSession) => Promise<T>): Promise<void> {
+  // 会话只在回调期间存在，结束（包括出错）后立即关闭；错误原样抛给调用方
+  async withLarkSession<T>(callback: (session: LarkSession) => Promise<T>, variant?: LarkVariantId): Promise<T> {
+    const session = await this.createLarkSession(variant)
     try {
-      const session = await this.createLarkSession()
-      await callback(session)
-      await session.close()
-    } catch {}
+      return await callback(session)
+    } finally {
+      await session.close().catch(() => {})
+    }
+  }
+
+  listLarkSessions(): Promise<LarkSessionInfo[]> {
+    return invoke<LarkSessionInfo[]>('list_lark_sessions')
+  }
+
+  isLarkRunning(variant?: LarkVariantId): Promise<boolean> {
+    return invoke<boolean>('is_lark_running', { variant })
+  }
+
+  killLark(variant?: LarkVariantId): Promise<void> {
+    return invoke('kill_lark', { variant })
+  }
+
+  waitUntilLarkEnded(variant?: LarkVariantId): Promise<void> {
+    return invoke('wait_until_lark_ended', { variant })
+  }
+
+  getLarkBasePath(variant?: LarkVariantId): Promise<string> {
+    return invoke<string>('get_lark_base_path', { variant })
+  }
+
+  // 丢弃缓存的飞书路径并重新查找
+  refreshLarkBasePath(variant?: LarkVariantId): Promise<string> {
+    return invoke<string>('refresh_lark_base_path', { variant })
+  }
+
+  getLarkLocation(variant?: LarkVariantId): Promise<LarkLocation> {
+    return invoke<LarkLocation>('get_lark_location', { variant })
+  }
+
+  getLarkVariants(): Promise<LarkVariant[]> {
+    return invoke<LarkVariant[]>('get_lark_variants')
+  }
+
+  // 检测每种客户端是否安装、装在哪里、是否正在运行
+  detectLarkVariants(): Promise<DetectedLarkVariant[]> {
+    return invoke<DetectedLarkVariant[]>('detect_lark_variants')
+  }
+
+  saveCustomLarkVariants(variants: LarkVariant[]): Promise<void> {
+    return invoke('save_custom_lark_variants', { variants })
+  }
+
+  validateLarkInstallDirectory(path: string, variant?: LarkVariantId): Promise<LarkInstallValidation> {
+    return invoke<LarkInstallValidation>('validate_lark_install_directory', { variant, path })
+  }
+
+  // 弹出目录选择框并校验选中的目录；取消时返回 null
+  pickLarkInstallDirectory(variant?: LarkVariantId): Promise<LarkInstallValidation | null> {
+    return invoke<LarkInstallValidation | null>('pick_lark_install_directory', { variant })
+  }
+
+  getLarkPathOverride(variant?: LarkVariantId): Promise<string | null> {
+    return invoke<string | null>('get_lark_path_override', { variant })
   }
 
-  isLarkRunning(): Promise<boolean> {
-    return invoke<boolean>('is_lark_running')
+  // 保存手动指定的安装目录（传 null 清除），返回重新查找后的版本目录
+  setLarkPathOverride(path: string | null, variant?: LarkVariantId): Promise<string> {
+    return invoke<string>('set_lark_path_override', { variant, path })
   }
 
-  killLark(): Promise<void> {
-    return invoke('kill_lark')
+  launchLark(variant?: LarkVariantId): Promise<void> {
+    return invoke('launch_lark', { variant })
   }
 
-  waitUntilLarkEnded(): Promise<void> {
-    return invoke('wait_until_lark_ended')
+  // asar 的完整目录树，供选择 innerPath 与 subject
+  listAsarTree(asarPath: string, variant?: LarkVariantId): Promise<AsarTreeNode> {
+    return invoke<AsarTreeNode>('list_asar_tree', { variant, asarPath })
   }
 
-  getLarkBasePath(): Promise<string> {
-    return invoke<string>('get_lark_base_path')
+  readAsarText(asarPath: string, innerPath: string, variant?: LarkVariantId): Promise<string> {
+    return invoke<string>('read_asar_text', { variant, asarPath, innerPath })
   }
 
-  launchLark(): Promise<void> {
-    return invoke('launch_lark')
+  searchAsar(asarPath: string, query: AsarSearchQuery, variant?: LarkVariantId): Promise<AsarSearchMatch[]> {
+    return invoke<AsarSearchMatch[]>('search_asar', { variant, asarPath, query })
   }
 
-  openLarkInstallDirectory(): Promise<void> {
-    return invoke('open_lark_install_directory')
+  // 一次性检查安装、asar、补丁标记、备份、主题库与进程状态
+  runLarkDoctor(variant?: LarkVariantId): Promise<DoctorReport> {
+    return invoke<DoctorReport>('run_lark_doctor', { variant })
+  }
+
+  // 导出诊断包（zip）；logs 为日志面板中的最近日志，redactScripts 为真时隐去主题中的脚本、自定义样式和样式表地址，并把路径中的用户目录改写为 %USERPROFILE%
+  exportDiagnosticBundle(payload: {
+    outputPath: string
+    logs: string[]
+    redactScripts: boolean
+    variant?: LarkVariantId
+  }): Promise<DoctorReport> {
+    return invoke<DoctorReport>('export_diagnostic_bundle', payload)
+  }
+
+  // 选择诊断包的保存位置；取消时返回 null
+  pickDiagnosticBundlePath(): Promise<string | null> {
+    return invoke<string | null>('pick_diagnostic_bundle_path')
+  }
+
+  openLarkInstallDirectory(variant?: LarkVariantId): Promise<void> {
+    return invoke('open_lark_install_directory', { variant })
   }
 
   // 弹出文件选择框并读取选中 YAML 的内容（不写盘）。返回内容与回退 id；取消时返回 null。
@@ -127,6 +510,11 @@ export class NativeBridge {
     return invoke<PickedTheme | null>('pick_theme_file')
   }
 
+  // 弹出保存对话框选择导出 asar 的位置；取消时返回 null
+  pickAsarExportPath(fileName?: string): Promise<string | null> {
+    return invoke<string | null>('pick_asar_export_path', { fileName })
+  }
+
   // 按 id 派生文件名写入托管目录（同名覆盖），返回实际文件名。
   saveTheme(id: string, content: string): Promise<string> {
     return invoke<string>('save_theme', { id, content })
@@ -149,8 +537,68 @@ export class NativeBridge {
     return invoke('write_theme_manifest', { content })
   }
 
-  subscribeToLogEvents(callback: (message: string) => void): ReturnType<typeof listen<string>> {
-    return listen<string>('log-events', (event) => {
+  // 序号大于 since 的原生端日志
+  getLogHistory(since?: number): Promise<LogRecord[]> {
+    return invoke<LogRecord[]>('get_log_history', { since })
+  }
+
+  // 先补齐序号大于 since 的历史日志，再按序转发新日志；补齐期间到达的日志暂存后去重
+  async subscribeToLogEvents(
+    since: number,
+    callback: (record: LogRecord) => void,
+  ): ReturnType<typeof listen<LogRecord>> {
+    let last = since
+    let pending: LogRecord[] | null = []
+    const deliver = (record: LogRecord) => {
+      if (record.seq > last) {
+        last = record.seq
+        callback(record)
+      }
+    }
+    const unlisten = await listen<LogRecord>('log-events', (event) => {
+      if (pending) {
+        pending.push(event.payload)
+      } else {
+        deliver(event.payload)
+      }
+    })
+    try {
+      const history = await this.getLogHistory(since)
+      history.forEach(deliver)
+    } finally {
+      pending.forEach(deliver)
+      pending = null
+    }
+    return unlisten
+  }
+
+  getLogLevel(): Promise<LogLevel> {
+    return invoke<LogLevel>('get_log_level')
+  }
+
+  // 原生端日志级别，低于该级别的日志不会写入文件或转发到日志面板；会保存到设置
+  setLogLevel(level: LogLevel): Promise<void> {
+    return invoke('set_log_level', { level })
+  }
+
+  // 启动时原生端会恢复上次被中断的应用；事件可能早于前端订阅，因此提供查询
+  getRecoveredApplies(): Promise<RecoveredApply[]> {
+    return invoke<RecoveredApply[]>('get_recovered_applies')
+  }
+
+  // 上次运行崩溃时的记录；没有崩溃时为 null
+  getPreviousCrash(): Promise<CrashReport | null> {
+    return invoke<CrashReport | null>('get_previous_crash')
+  }
+
+  subscribeToNativePanics(callback: (report: CrashReport) => void): ReturnType<typeof listen<CrashReport>> {
+    return listen<CrashReport>('native-panic', (event) => {
+      callback(event.payload)
+    })
+  }
+
+  subscribeToLarkPathChanges(callback: (change: LarkPathChange) => void): ReturnType<typeof listen<LarkPathChange>> {
+    return listen<LarkPathChange>('lark-path-changed', (event) => {
       callback(event.payload)
     })
   }
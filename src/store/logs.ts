@@ -1,4 +1,5 @@
 import { create } from 'zustand'
+import type { LogRecord } from '../ports/bridge'
 
 export interface Log {
   text: string
@@ -7,12 +8,16 @@ export interface Log {
 
 export interface LogsStore {
   logs: Log[]
+  /** 已收到的最后一条原生端日志的序号，重新订阅时从这里补齐 */
+  lastNativeSeq: number
   add: (text: string) => void
+  addNative: (record: LogRecord) => void
   clear: () => void
 }
 
 export const useLogsStore = create<LogsStore>((set) => ({
   logs: [],
+  lastNativeSeq: 0,
   add: (text: string) => {
     const components = text.split(' ')
     if (isAllCapitalized(components[0])) {
@@ -27,6 +32,20 @@ export const useLogsStore = create<LogsStore>((set) => ({
     }
     set((state) => ({ logs: [...state.logs, log] }))
   },
+  addNative: (record: LogRecord) => {
+    const fields = Object.entries(record.fields).map(([key, value]) => ` ${key}=${value}`)
+    const level = record.level === 'info' ? '' : `${record.level.toUpperCase()} `
+    const log: Log = {
+      timestamp: record.timestamp,
+      text: `[NATIVE] ${level}${record.message}${fields.join('')}`,
+    }
+    set((state) => {
+      if (record.seq <= state.lastNativeSeq) {
+        return state
+      }
+      return { logs: [...state.logs, log], lastNativeSeq: record.seq }
+    })
+  },
   clear: () => set({ logs: [] }),
 }))
 
@@ -1,6 +1,6 @@
 import { describe, expect, it } from 'vitest'
 import { mergeThemes } from './merge'
-import { WalAsarPatchFile, WalAsarPatchMainScript, WalTheme } from './types'
+import { WalAsarPatchFile, WalAsarPatchHtml, WalAsarPatchMainScript, WalTheme } from './types'
 
 function fileTheme(asar: string, patch: Partial<WalAsarPatchFile> & { path: string }): WalTheme {
   return {
@@ -102,6 +102,50 @@ describe('mergeThemes', () => {
     expect(patches[0].colorOverrides).toEqual({ '--c': 'red' }) // index 0 wins
   })
 
+  it('targets all entry scripts when any merged main-script patch asks for it', () => {
+    const mk = (allEntries: boolean): WalTheme => ({
+      asarPatches: {
+        'a.asar': [
+          {
+            kind: 'main-script',
+            subject: 'main',
+            styleOverridesBySelector: {},
+            colorOverrides: {},
+            enableDevTools: false,
+            customScript: '',
+            allEntries,
+          } as WalAsarPatchMainScript,
+        ],
+      },
+    })
+
+    const merged = mergeThemes([mk(false), mk(true)])
+    expect((merged.asarPatches['a.asar'][0] as WalAsarPatchMainScript).allEntries).toBe(true)
+  })
+
+  it('unions stylesheets and concatenates custom css when merging html patches', () => {
+    const mk = (href: string, customCss: string): WalTheme => ({
+      asarPatches: {
+        'a.asar': [
+          {
+            kind: 'html',
+            subject: 'main',
+            styleOverridesBySelector: {},
+            colorOverrides: {},
+            enableDevTools: false,
+            customCss,
+            stylesheets: ['shared.css', href],
+          } as WalAsarPatchHtml,
+        ],
+      },
+    })
+
+    const merged = mergeThemes([mk('high.css', 'HIGH'), mk('low.css', 'LOW')])
+    const p = merged.asarPatches['a.asar'][0] as WalAsarPatchHtml
+    expect(p.stylesheets).toEqual(['shared.css', 'low.css', 'high.css'])
+    expect(p.customCss).toBe('LOW\nHIGH')
+  })
+
   it('does not mutate the input themes', () => {
     const t = fileTheme('a.asar', { path: 'index.js', colorOverrides: { '--x': '1' } })
     const snapshot = JSON.parse(JSON.stringify(t))
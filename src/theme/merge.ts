@@ -6,7 +6,16 @@ import { WalAsarPatch, WalAsarPatchBase, WalTheme } from './types'
  * 同 targetKey 的补丁必须合并，否则后者会整块覆盖前者。
  */
 function targetKey(patch: WalAsarPatch): string {
-  return patch.kind === 'file' ? `file::${patch.path}` : `main-script::${patch.subject}`
+  switch (patch.kind) {
+    case 'file':
+      return `file::${patch.path}`
+    case 'css':
+      return `css::${patch.path}`
+    case 'html':
+      return `html::${patch.subject}`
+    default:
+      return `main-script::${patch.subject}`
+  }
 }
 
 function mergeStyles(
@@ -31,6 +40,10 @@ function concatScript(lower?: string, higher?: string): string {
     .join('\n')
 }
 
+function mergeStylesheets(lower: string[] = [], higher: string[] = []): string[] {
+  return [...new Set([...lower, ...higher])]
+}
+
 /** 把同一目标文件上的两个补丁合并，higher 的冲突项胜出。 */
 function mergePatch(lower: WalAsarPatch, higher: WalAsarPatch): WalAsarPatch {
   const base: WalAsarPatchBase = {
@@ -38,13 +51,20 @@ function mergePatch(lower: WalAsarPatch, higher: WalAsarPatch): WalAsarPatch {
     colorOverrides: { ...(lower.colorOverrides ?? {}), ...(higher.colorOverrides ?? {}) },
     enableDevTools: Boolean(lower.enableDevTools) || Boolean(higher.enableDevTools),
     customScript: concatScript(lower.customScript, higher.customScript),
+    customCss: concatScript(lower.customCss, higher.customCss),
     description: higher.description || lower.description,
   }
   // 同 targetKey 意味着 kind 与 path/subject 必定一致，沿用 higher 的即可
   if (higher.kind === 'main-script') {
-    return { ...base, kind: 'main-script', subject: higher.subject }
+    const allEntries = (lower.kind === 'main-script' && Boolean(lower.allEntries)) || Boolean(higher.allEntries)
+    return { ...base, kind: 'main-script', subject: higher.subject, ...(allEntries ? { allEntries } : {}) }
   }
-  return { ...base, kind: 'file', path: higher.path }
+  if (higher.kind === 'html') {
+    const lowerSheets = lower.kind === 'html' ? lower.stylesheets : []
+    const stylesheets = mergeStylesheets(lowerSheets, higher.stylesheets)
+    return { ...base, kind: 'html', subject: higher.subject, stylesheets }
+  }
+  return { ...base, kind: higher.kind, path: higher.path }
 }
 
 /** 把一个补丁规范化为不与源对象共享引用的副本（字段补全为空默认值）。 */
@@ -54,12 +74,16 @@ function clonePatch(patch: WalAsarPatch): WalAsarPatch {
     colorOverrides: { ...(patch.colorOverrides ?? {}) },
     enableDevTools: Boolean(patch.enableDevTools),
     customScript: (patch.customScript ?? '').trim(),
+    customCss: (patch.customCss ?? '').trim(),
     description: patch.description,
   }
   if (patch.kind === 'main-script') {
-    return { ...base, kind: 'main-script', subject: patch.subject }
+    return { ...base, kind: 'main-script', subject: patch.subject, ...(patch.allEntries ? { allEntries: true } : {}) }
+  }
+  if (patch.kind === 'html') {
+    return { ...base, kind: 'html', subject: patch.subject, stylesheets: [...(patch.stylesheets ?? [])] }
   }
-  return { ...base, kind: 'file', path: patch.path }
+  return { ...base, kind: patch.kind, path: patch.path }
 }
 
 /**
@@ -3,12 +3,16 @@ export interface WalAsarPatchBase {
   colorOverrides: Record<string, string>
   enableDevTools: boolean
   customScript?: string
+  /** 原样写入的 css，仅用于 css 与 html 补丁 */
+  customCss?: string
   description?: string
 }
 
 export interface WalAsarPatchMainScript extends WalAsarPatchBase {
   kind: 'main-script'
   subject: string
+  /** 给 index.html 引用的每个脚本都打补丁；默认只打入口脚本 */
+  allEntries?: boolean
 }
 
 export interface WalAsarPatchFile extends WalAsarPatchBase {
@@ -16,7 +20,20 @@ export interface WalAsarPatchFile extends WalAsarPatchBase {
   path: string
 }
 
-export type WalAsarPatch = WalAsarPatchMainScript | WalAsarPatchFile
+/** 样式直接追加到 asar 内的 .css 文件，不经过脚本，加载时不会闪烁 */
+export interface WalAsarPatchCss extends WalAsarPatchBase {
+  kind: 'css'
+  path: string
+}
+
+/** 样式以 <style> 插入 subject 目录下的 index.html，stylesheets 中的地址以 <link> 引入 */
+export interface WalAsarPatchHtml extends WalAsarPatchBase {
+  kind: 'html'
+  subject: string
+  stylesheets?: string[]
+}
+
+export type WalAsarPatch = WalAsarPatchMainScript | WalAsarPatchFile | WalAsarPatchCss | WalAsarPatchHtml
 
 export interface WalTheme {
   /** 主题唯一标识。导入时同 id 直接覆盖；缺省时回退用源文件名。 */